@@ -13,6 +13,14 @@ defmodule Tokenizers.Decoder do
 
   @type t() :: %__MODULE__{resource: reference()}
 
+  @doc """
+  Computes a fingerprint of the decoder configuration.
+
+  See `Tokenizers.Tokenizer.fingerprint/2`.
+  """
+  @spec fingerprint(t()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(decoder), to: Tokenizers.Native, as: :decoders_fingerprint
+
   @doc """
   Decodes tokens into string with provided decoder.
   """
@@ -88,9 +96,15 @@ defmodule Tokenizers.Decoder do
 
   @doc """
   Creates a Replace decoder.
+
+  Raises `Tokenizers.Error` if the pattern is invalid.
   """
   @spec replace(String.t(), String.t()) :: t()
-  defdelegate replace(pattern, content), to: Tokenizers.Native, as: :decoders_replace
+  def replace(pattern, content) do
+    pattern
+    |> Tokenizers.Native.decoders_replace(content)
+    |> Tokenizers.Shared.unwrap()
+  end
 
   @doc """
   Combines a list of decoders into a single sequential decoder.
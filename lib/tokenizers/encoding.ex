@@ -251,6 +251,168 @@ defmodule Tokenizers.Encoding do
   Also, you can build this list manually, as long as it follows the format.
   """
   defdelegate transform(encoding, transformations), to: Tokenizers.Native, as: :encoding_transform
+
+  @typedoc """
+  An entity span aggregated from per-token predictions.
+
+  `:start` and `:end` are UTF-8 byte offsets into the original text.
+  """
+  @type entity :: %{
+          label: String.t(),
+          score: float(),
+          start: non_neg_integer(),
+          end: non_neg_integer(),
+          text: String.t()
+        }
+
+  @doc """
+  Aggregates per-token predictions of a token classifier into entity
+  spans over the original `text`.
+
+  `encoding` is either a single encoding, in which case its overflowing
+  windows are included, or a list of windows. `predictions` is a binary
+  with one row per token of all windows concatenated, and `labels` maps
+  each label id to its name, such as `"B-PER"` or `"I-PER"`.
+
+  Subword tokens are first grouped into words, then consecutive words
+  with the same entity type are merged, unless a word starts with a
+  `B-` label. Tokens seen in more than one window, as happens when
+  truncating with a stride, have their scores averaged.
+
+  ## Options
+
+    * `:aggregation_strategy` - how the predictions of subword tokens
+      are combined into a word. One of:
+
+        * `:simple` - no word grouping, every token is its own unit
+
+        * `:first` - the word takes the label of its first token
+
+        * `:average` - the scores are averaged over the word tokens
+
+        * `:max` - the word takes the label of its highest scoring token
+
+      Defaults to `:first`
+
+    * `:predictions_type` - either `:scores` for a row-major matrix of
+      native f32 scores with shape `{tokens, labels}`, or `:label_ids`
+      for a native u32 label id per token. Defaults to `:scores`
+
+    * `:softmax` - whether to apply softmax to each row of scores.
+      Defaults to `true`
+
+    * `:ignore_labels` - labels that are not part of any entity.
+      Defaults to `["O"]`
+
+    * `:sequence_id` - the input sequence to extract entities from.
+      Defaults to `0`
+
+  """
+  @spec aggregate_entities(t() | [t()], String.t(), binary(), [String.t()], keyword()) ::
+          {:ok, [entity()]} | {:error, term()}
+  def aggregate_entities(encoding, text, predictions, labels, opts \\ []) do
+    Tokenizers.Native.encoding_aggregate_entities(
+      windows(encoding),
+      text,
+      predictions,
+      labels,
+      opts
+    )
+  end
+
+  @doc """
+  Maps an answer span in the context to start and end token positions
+  in every window of `encoding`.
+
+  This is meant for preparing extractive question answering training
+  data, where the question and the context are encoded as a pair and
+  truncated with a stride. `encoding` is either a single encoding, in
+  which case its overflowing windows are included, or a list of windows.
+  `answer` is a `{start, end}` tuple of UTF-8 byte offsets in the
+  context.
+
+  Returns a map per window with the `:start` and `:end` token positions,
+  both inclusive. When the answer is not fully contained in the context
+  of a window, it is marked as `:impossible` and both positions point to
+  the impossible index.
+
+  ## Options
+
+    * `:context_sequence_id` - the sequence id of the context. Defaults
+      to `1`
+
+    * `:impossible_index` - the token position used for impossible
+      answers. Defaults to `0`, the position of the CLS token in most
+      models
+
+  """
+  @spec answer_token_positions(t() | [t()], {non_neg_integer(), non_neg_integer()}, keyword()) ::
+          [%{start: non_neg_integer(), end: non_neg_integer(), impossible: boolean()}]
+  def answer_token_positions(encoding, answer, opts \\ []) do
+    Tokenizers.Native.encoding_answer_token_positions(windows(encoding), answer, opts)
+  end
+
+  @doc """
+  Finds the best answer spans in the context given the start and end
+  logits predicted for every window of `encoding`.
+
+  `encoding` is either a single encoding, in which case its overflowing
+  windows are included, or a list of windows. `start_logits` and
+  `end_logits` are binaries with a native f32 logit per token of all
+  windows concatenated.
+
+  Candidate spans are scored as the sum of their start and end logits.
+  Only spans within the context of a single window are considered, and
+  a span predicted from several overlapping windows is returned once,
+  with its best score. `:start` and `:end` are UTF-8 byte offsets in
+  `context` and `:window` is the index of the window the span comes
+  from.
+
+  ## Options
+
+    * `:context_sequence_id` - the sequence id of the context. Defaults
+      to `1`
+
+    * `:max_answer_length` - the maximum number of tokens in an answer.
+      Defaults to `30`
+
+    * `:n_best` - the number of top start and end logits considered in
+      every window. Defaults to `20`
+
+    * `:top_k` - the number of answers to return. Defaults to `1`
+
+    * `:handle_impossible_answer` - whether an empty answer can be
+      returned. Its score is the lowest score of the impossible index
+      across windows. Defaults to `false`
+
+    * `:impossible_index` - the token position of the impossible answer.
+      Defaults to `0`
+
+  """
+  @spec answer_spans(t() | [t()], String.t(), binary(), binary(), keyword()) ::
+          {:ok,
+           [
+             %{
+               score: float(),
+               start: non_neg_integer(),
+               end: non_neg_integer(),
+               text: String.t(),
+               window: non_neg_integer()
+             }
+           ]}
+          | {:error, term()}
+  def answer_spans(encoding, context, start_logits, end_logits, opts \\ []) do
+    Tokenizers.Native.encoding_answer_spans(
+      windows(encoding),
+      context,
+      start_logits,
+      end_logits,
+      opts
+    )
+  end
+
+  defp windows(%__MODULE__{} = encoding), do: [encoding | get_overflowing(encoding)]
+  defp windows(windows) when is_list(windows), do: windows
 end
 
 defimpl Inspect, for: Tokenizers.Encoding do
@@ -0,0 +1,49 @@
+defmodule Tokenizers.Error do
+  @moduledoc """
+  An error returned by the native functions of this library.
+
+  Functions returning `{:error, reason}` give an error of this type as
+  reason, so that callers can match on its `:kind`, while functions
+  that raise do it with this exception.
+
+  ## Fields
+
+    * `:kind` - the category of the error, one of:
+
+      * `:io` - reading or writing a file failed
+      * `:json_parse` - a serialized tokenizer or component is invalid
+      * `:invalid_pattern` - a regular expression does not compile
+      * `:invalid_template` - a post-processing template is malformed
+      * `:unknown_token` - a token or id is not in the vocabulary
+      * `:invalid_char` - an integer is not a valid Unicode codepoint
+      * `:training_panic` - training failed unexpectedly
+      * `:tokenizer` - any other error of the tokenization pipeline
+      * `:internal` - a bug in this library
+      * `:other` - invalid input, such as inconsistent options
+      * `:unknown` - any other unexpected error
+
+    * `:message` - a human-readable description
+
+    * `:context` - details depending on the kind. For `:json_parse`,
+      the `:path` of the offending field, such as `"model.vocab"`, and
+      its `:line` and `:column` in the source
+
+  """
+
+  defexception [:kind, :message, context: %{}]
+
+  @type kind() ::
+          :io
+          | :json_parse
+          | :invalid_pattern
+          | :invalid_template
+          | :unknown_token
+          | :invalid_char
+          | :training_panic
+          | :tokenizer
+          | :internal
+          | :other
+          | :unknown
+
+  @type t() :: %__MODULE__{kind: kind(), message: String.t(), context: map()}
+end
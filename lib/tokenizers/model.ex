@@ -18,6 +18,67 @@ defmodule Tokenizers.Model do
   @spec info(t()) :: map()
   defdelegate info(model), to: Tokenizers.Native, as: :models_info
 
+  @doc """
+  Computes a fingerprint of the model, including its vocabulary, merges
+  and scores.
+
+  See `Tokenizers.Tokenizer.fingerprint/2`.
+  """
+  @spec fingerprint(t()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(model), to: Tokenizers.Native, as: :models_fingerprint
+
+  @typedoc """
+  A token produced by the model.
+
+  Offsets are byte offsets within the tokenized sequence.
+  """
+  @type token() :: %{
+          id: non_neg_integer(),
+          value: String.t(),
+          offsets: {non_neg_integer(), non_neg_integer()}
+        }
+
+  @doc """
+  Tokenizes the given sequence with the model alone.
+
+  No normalization, pre-tokenization or post-processing is applied,
+  so the sequence is usually a single, already pre-tokenized word.
+  """
+  @spec tokenize(t(), String.t()) :: {:ok, [token()]} | {:error, term()}
+  defdelegate tokenize(model, sequence), to: Tokenizers.Native, as: :models_tokenize
+
+  @doc """
+  Batched version of `tokenize/2`.
+  """
+  @spec tokenize_batch(t(), [String.t()]) :: {:ok, [[token()]]} | {:error, term()}
+  defdelegate tokenize_batch(model, sequences),
+    to: Tokenizers.Native,
+    as: :models_tokenize_batch
+
+  @doc """
+  Converts the given token to its id in the model vocabulary.
+  """
+  @spec token_to_id(t(), String.t()) :: non_neg_integer() | nil
+  defdelegate token_to_id(model, token), to: Tokenizers.Native, as: :models_token_to_id
+
+  @doc """
+  Converts the given id to its token in the model vocabulary.
+  """
+  @spec id_to_token(t(), non_neg_integer()) :: String.t() | nil
+  defdelegate id_to_token(model, id), to: Tokenizers.Native, as: :models_id_to_token
+
+  @doc """
+  Returns the model vocabulary.
+  """
+  @spec get_vocab(t()) :: %{String.t() => non_neg_integer()}
+  defdelegate get_vocab(model), to: Tokenizers.Native, as: :models_get_vocab
+
+  @doc """
+  Returns the size of the model vocabulary.
+  """
+  @spec get_vocab_size(t()) :: non_neg_integer()
+  defdelegate get_vocab_size(model), to: Tokenizers.Native, as: :models_get_vocab_size
+
   @doc """
   Saves the given model in the given directory.
 
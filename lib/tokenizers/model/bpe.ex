@@ -54,4 +54,48 @@ defmodule Tokenizers.Model.BPE do
   defdelegate from_file(vocab_path, merges_path, options \\ []),
     to: Tokenizers.Native,
     as: :models_bpe_from_file
+
+  @doc """
+  Returns the merges of the model in rank order.
+  """
+  @spec merges(Tokenizers.Model.t()) :: {:ok, [{String.t(), String.t()}]} | {:error, term()}
+  defdelegate merges(model), to: Tokenizers.Native, as: :models_bpe_merges
+
+  @typedoc """
+  How a word was tokenized by the model.
+
+    * `:symbols` - the initial symbols of the word, before any merge
+
+    * `:merges` - the merges applied, in order, each with the merged
+      `:pair`, its `:rank` and the resulting `:token`
+
+    * `:tokens` - the final tokens, as in `Tokenizers.Model.tokenize/2`
+
+    * `:fallbacks` - the chars missing from the vocabulary, each with
+      its `:kind` (`:byte_fallback` or `:unk`), its `:text` and
+      `:offsets`, and the `:tokens` it became. Consecutive chars fused
+      into a single unknown token are reported together
+
+  """
+  @type explanation() :: %{
+          symbols: [String.t()],
+          merges: [%{pair: {String.t(), String.t()}, rank: non_neg_integer(), token: String.t()}],
+          tokens: [Tokenizers.Model.token()],
+          fallbacks: [
+            %{
+              kind: :byte_fallback | :unk,
+              text: String.t(),
+              offsets: {non_neg_integer(), non_neg_integer()},
+              tokens: [String.t()]
+            }
+          ]
+        }
+
+  @doc """
+  Explains how the model tokenizes the given word.
+
+  Dropout set on the model is not applied.
+  """
+  @spec explain(Tokenizers.Model.t(), String.t()) :: {:ok, explanation()} | {:error, term()}
+  defdelegate explain(model, word), to: Tokenizers.Native, as: :models_bpe_explain
 end
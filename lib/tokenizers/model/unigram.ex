@@ -2,8 +2,11 @@ defmodule Tokenizers.Model.Unigram do
   @typedoc """
   Options for model initialisation.
 
-    * `:byte_fallback`- whether to use the byte fallback trick
-    * `:unk_id`- the unknown token id to be used by the model
+    * `:byte_fallback`- whether to use the byte fallback trick.
+      Defaults to `false`
+
+    * `:unk_id`- the unknown token id to be used by the model.
+      Defaults to no unknown token
 
   """
   @type options() :: [
@@ -24,4 +27,52 @@ defmodule Tokenizers.Model.Unigram do
   """
   @spec empty() :: {:ok, Tokenizers.Model.t()}
   defdelegate empty(), to: Tokenizers.Native, as: :models_unigram_empty
+
+  @doc """
+  Instantiate a Unigram model from the given file.
+
+  The file is expected in the format written by `Tokenizers.Model.save/2`,
+  a JSON object with the `"vocab"` pairs, `"unk_id"` and `"byte_fallback"`.
+  """
+  @spec from_file(String.t()) :: {:ok, Tokenizers.Model.t()} | {:error, term()}
+  defdelegate from_file(path), to: Tokenizers.Native, as: :models_unigram_from_file
+
+  @doc """
+  Returns the vocabulary of the model as `{piece, score}` pairs in id order.
+  """
+  @spec vocab(Tokenizers.Model.t()) :: {:ok, [{String.t(), float()}]} | {:error, term()}
+  defdelegate vocab(model), to: Tokenizers.Native, as: :models_unigram_vocab
+
+  @typedoc """
+  Likelihood scores of a text.
+
+    * `:viterbi` - the log-probability of the best segmentation
+    * `:marginal` - the log-probability of the text, summed over all
+      its segmentations
+    * `:perplexity` - the perplexity per piece of the best segmentation
+    * `:pieces` - the pieces of the best segmentation with their score
+
+  """
+  @type score() :: %{
+          viterbi: float(),
+          marginal: float(),
+          perplexity: float(),
+          pieces: [{String.t(), float()}]
+        }
+
+  @doc """
+  Scores the likelihood of the given text under the model.
+
+  The text is scored as a whole, without normalization or
+  pre-tokenization. Unknown characters are scored with the model
+  penalty for unknown pieces.
+  """
+  @spec score(Tokenizers.Model.t(), String.t()) :: {:ok, score()} | {:error, term()}
+  defdelegate score(model, text), to: Tokenizers.Native, as: :models_unigram_score
+
+  @doc """
+  Batched version of `score/2`.
+  """
+  @spec score_batch(Tokenizers.Model.t(), [String.t()]) :: {:ok, [score()]} | {:error, term()}
+  defdelegate score_batch(model, texts), to: Tokenizers.Native, as: :models_unigram_score_batch
 end
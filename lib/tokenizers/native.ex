@@ -21,6 +21,7 @@ defmodule Tokenizers.Native do
   def decoders_decode(_decoder, _tokens), do: err()
   #
   def decoders_info(_decoder), do: err()
+  def decoders_fingerprint(_decoder), do: err()
   #
   def decoders_byte_level(), do: err()
   def decoders_replace(_pattern, _content), do: err()
@@ -68,15 +69,28 @@ defmodule Tokenizers.Native do
   def encoding_truncate(_encoding, _max_length, _opts), do: err()
   #
   def encoding_transform(_encoding, _transformers), do: err()
+  #
+  def encoding_aggregate_entities(_encodings, _text, _predictions, _labels, _opts), do: err()
+  def encoding_answer_token_positions(_encodings, _answer, _opts), do: err()
+  def encoding_answer_spans(_encodings, _context, _start_logits, _end_logits, _opts), do: err()
 
   # Models
   def models_save(_model, _folder, _opts), do: err()
   #
   def models_info(_model), do: err()
+  def models_fingerprint(_model), do: err()
+  def models_tokenize(_model, _sequence), do: err()
+  def models_tokenize_batch(_model, _sequences), do: err()
+  def models_token_to_id(_model, _token), do: err()
+  def models_id_to_token(_model, _id), do: err()
+  def models_get_vocab(_model), do: err()
+  def models_get_vocab_size(_model), do: err()
   #
   def models_bpe_init(_vocab, _merges, _options), do: err()
   def models_bpe_empty(), do: err()
   def models_bpe_from_file(_vocab, _merges, _options), do: err()
+  def models_bpe_merges(_model), do: err()
+  def models_bpe_explain(_model, _word), do: err()
   #
   def models_wordpiece_init(_vocab, _options), do: err()
   def models_wordpiece_empty(), do: err()
@@ -88,11 +102,17 @@ defmodule Tokenizers.Native do
   #
   def models_unigram_init(_vocab, _options), do: err()
   def models_unigram_empty(), do: err()
+  def models_unigram_from_file(_path), do: err()
+  def models_unigram_vocab(_model), do: err()
+  def models_unigram_score(_model, _text), do: err()
+  def models_unigram_score_batch(_model, _texts), do: err()
 
   # Normalizers
   def normalizers_normalize(_normalizer, _input), do: err()
+  def normalizers_normalize_with_alignments(_normalizer, _input, _options), do: err()
   #
   def normalizers_info(_normalizer), do: err()
+  def normalizers_fingerprint(_normalizer), do: err()
   #
   def normalizers_bert_normalizer(_opts), do: err()
   def normalizers_nfd(), do: err()
@@ -110,10 +130,14 @@ defmodule Tokenizers.Native do
   def normalizers_byte_level(), do: err()
   def normalizers_byte_level_alphabet(), do: err()
 
+  # Packing
+  def packing_pack(_documents, _length, _opts), do: err()
+
   # PreTokenizers
   def pre_tokenizers_pre_tokenize(_pre_tokenizer, _input), do: err()
   #
   def pre_tokenizers_info(_pre_tokenizer), do: err()
+  def pre_tokenizers_fingerprint(_pre_tokenizer), do: err()
   #
   def pre_tokenizers_byte_level(_opts), do: err()
   def pre_tokenizers_byte_level_alphabet(), do: err()
@@ -129,6 +153,7 @@ defmodule Tokenizers.Native do
 
   # PostProcessors
   def post_processors_info(_post_processor), do: err()
+  def post_processors_fingerprint(_post_processor), do: err()
   #
   def post_processors_bert(_sep, _cls), do: err()
   def post_processors_roberta(_sep, _cls, _opts), do: err()
@@ -168,16 +193,34 @@ defmodule Tokenizers.Native do
   def tokenizer_disable_truncation(_tokenizer), do: err()
   def tokenizer_set_padding(_tokenizer, _opts), do: err()
   def tokenizer_disable_padding(_tokenizer), do: err()
+  def tokenizer_set_encode_special_tokens(_tokenizer, _value), do: err()
+  def tokenizer_get_encode_special_tokens(_tokenizer), do: err()
   #
   def tokenizer_encode(_tokenizer, _input, _options), do: err()
   def tokenizer_encode_batch(_tokenizer, _inputs, _options), do: err()
+  def tokenizer_encode_nbest(_tokenizer, _input, _n, _options), do: err()
+  def tokenizer_encode_bucketed(_tokenizer, _inputs, _max_tokens, _options), do: err()
+  def tokenizer_encode_chat(_tokenizer, _segments, _options), do: err()
+  def tokenizer_encode_multi(_tokenizer, _sequences, _options), do: err()
+  def tokenizer_encode_with_placeholders(_tokenizer, _input, _placeholders, _options), do: err()
+  def tokenizer_trace(_tokenizer, _input, _options), do: err()
   def tokenizer_decode(_tokenizer, _ids, _options), do: err()
   def tokenizer_decode_batch(_tokenizer, _ids, _options), do: err()
   def tokenizer_token_to_id(_tokenizer, _token), do: err()
   def tokenizer_id_to_token(_tokenizer, _id), do: err()
   def tokenizer_post_processing(_tokenizer, _encoding, _pair, _add_special_tokens), do: err()
+  def tokenizer_diff(_left, _right, _options), do: err()
+  def tokenizer_validate(_tokenizer), do: err()
+  def tokenizer_fingerprint(_tokenizer, _options), do: err()
   #
   def tokenizer_train_from_files(_tokenizer, _files, _trainer), do: err()
 
+  # Vocabulary
+  def vocabulary_prune(_tokenizer, _corpus, _options), do: err()
+  def vocabulary_extend(_tokenizer, _corpus, _size, _options), do: err()
+  def vocabulary_rename(_tokenizer, _id, _content), do: err()
+  def vocabulary_update_added_token(_tokenizer, _id, _options), do: err()
+  def vocabulary_remove_added_tokens(_tokenizer, _ids), do: err()
+
   defp err(), do: :erlang.nif_error(:nif_not_loaded)
 end
@@ -16,12 +16,121 @@ defmodule Tokenizers.Normalizer do
 
   @type t() :: %__MODULE__{resource: reference()}
 
+  @doc """
+  Computes a fingerprint of the normalizer configuration.
+
+  See `Tokenizers.Tokenizer.fingerprint/2`.
+  """
+  @spec fingerprint(t()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(normalizer), to: Tokenizers.Native, as: :normalizers_fingerprint
+
   @doc """
   Normalizes the given text input.
   """
   @spec normalize(t(), String.t()) :: {:ok, String.t()}
   defdelegate normalize(normalizer, input), to: Tokenizers.Native, as: :normalizers_normalize
 
+  @typedoc """
+  The result of `normalize_with_alignments/3`.
+
+  Every entry of `:alignments` is the `{start, end}` range in the
+  original input that the corresponding unit of the normalized string
+  was produced from.
+  """
+  @type alignments() :: %{
+          normalized: String.t(),
+          alignments: [{non_neg_integer(), non_neg_integer()}]
+        }
+
+  @doc """
+  Normalizes the given text input and returns the alignment of the
+  normalized string with the original input.
+
+  ## Options
+
+    * `:unit` - the unit of the alignments, either `:byte` or `:char`.
+      With `:byte` there is one entry for every byte of the normalized
+      string, holding original byte offsets. With `:char` there is one
+      entry for every char, holding original char offsets. Defaults
+      to `:byte`
+
+  """
+  @spec normalize_with_alignments(t(), String.t(), keyword()) ::
+          {:ok, alignments()} | {:error, term()}
+  defdelegate normalize_with_alignments(normalizer, input, opts \\ []),
+    to: Tokenizers.Native,
+    as: :normalizers_normalize_with_alignments
+
+  @doc """
+  Converts a `{start, end}` range in the normalized string to the
+  corresponding range in the original input.
+
+  The range is expressed in the unit the alignments were computed
+  with. Returns `nil` if the range is out of bounds.
+  """
+  @spec to_original(alignments(), {non_neg_integer(), non_neg_integer()}) ::
+          {non_neg_integer(), non_neg_integer()} | nil
+  def to_original(%{alignments: alignments}, {start, stop}) do
+    alignments = List.to_tuple(alignments)
+    size = tuple_size(alignments)
+
+    cond do
+      start > stop or stop > size ->
+        nil
+
+      start == stop ->
+        position =
+          cond do
+            start < size -> alignments |> elem(start) |> elem(0)
+            size > 0 -> alignments |> elem(size - 1) |> elem(1)
+            true -> 0
+          end
+
+        {position, position}
+
+      true ->
+        {original_start, _} = elem(alignments, start)
+
+        original_end =
+          Enum.reduce(start..(stop - 1)//1, 0, fn index, original_end ->
+            max(original_end, alignments |> elem(index) |> elem(1))
+          end)
+
+        {original_start, original_end}
+    end
+  end
+
+  @doc """
+  Converts a `{start, end}` range in the original input to the
+  corresponding range in the normalized string.
+
+  The result covers every unit of the normalized string that was
+  produced entirely from within the given range. When there is no
+  such unit, an empty range at the matching position is returned.
+  """
+  @spec to_normalized(alignments(), {non_neg_integer(), non_neg_integer()}) ::
+          {non_neg_integer(), non_neg_integer()}
+  def to_normalized(%{alignments: alignments}, {start, stop}) do
+    {first, last, before} =
+      alignments
+      |> Enum.with_index()
+      |> Enum.reduce({nil, nil, 0}, fn {{original_start, original_end}, index}, acc ->
+        {first, last, before} = acc
+        before = if original_start < start, do: before + 1, else: before
+
+        if original_start >= start and original_end <= stop do
+          {first || index, index, before}
+        else
+          {first, last, before}
+        end
+      end)
+
+    case first do
+      nil -> {before, before}
+      first -> {first, last + 1}
+    end
+  end
+
   # Normalizer entities. Following the order in https://docs.rs/tokenizers/0.20.0/src/tokenizers/normalizers/mod.rs.html#24
 
   @doc """
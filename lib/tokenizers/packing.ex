@@ -0,0 +1,76 @@
+defmodule Tokenizers.Packing do
+  @moduledoc """
+  Functions to pack many short documents into rows of a fixed length.
+
+  Packing avoids the compute wasted on padding when pretraining or
+  fine-tuning on short documents. Instead of padding every document to
+  the longest one, documents are placed one after another in rows of
+  exactly `length` tokens, and only the end of each row is padded.
+  """
+
+  @typedoc """
+  The packed rows.
+
+  All binaries hold native u32 values with `:shape`, that is
+  `{rows, length}`:
+
+    * `:ids` - the token ids, with padding at the end of each row
+
+    * `:attention_mask` - `1` for document tokens and `0` for padding
+
+    * `:position_ids` - positions that restart at `0` at the beginning
+      of every document in a row
+
+    * `:segment_ids` - the index of the document within its row,
+      starting at `1`, and `0` for padding. Tokens attend only to tokens
+      with the same segment id
+
+  `:spans` tells where every document landed. Documents longer than a
+  row are split into several spans, and `:offset` is the position of the
+  span within its document.
+  """
+  @type packed :: %{
+          ids: binary(),
+          attention_mask: binary(),
+          position_ids: binary(),
+          segment_ids: binary(),
+          shape: {non_neg_integer(), non_neg_integer()},
+          spans: [
+            %{
+              document: non_neg_integer(),
+              offset: non_neg_integer(),
+              row: non_neg_integer(),
+              start: non_neg_integer(),
+              length: non_neg_integer()
+            }
+          ]
+        }
+
+  @doc """
+  Packs `documents` into rows of exactly `length` tokens.
+
+  Every document is either a `t:Tokenizers.Encoding.t/0`, in which case
+  its padding is dropped, or a list of token ids. A document longer than
+  a row, separator included, is split into chunks of `length` tokens.
+
+  ## Options
+
+    * `:strategy` - the packing strategy. Defaults to `:greedy`. One of:
+
+        * `:greedy` - documents are placed in order, and a new row is
+          started whenever the next document does not fit
+
+        * `:first_fit_decreasing` - documents are sorted from longest to
+          shortest and each one is placed in the first row it fits in,
+          which usually results in fewer rows
+
+    * `:separator_id` - the id of the EOS or separator token appended
+      to every document. Defaults to `nil`, meaning no separator
+
+    * `:pad_id` - the id used to pad rows. Defaults to `0`
+
+  """
+  @spec pack([Tokenizers.Encoding.t() | [non_neg_integer()]], pos_integer(), keyword()) ::
+          {:ok, packed()} | {:error, term()}
+  defdelegate pack(documents, length, opts \\ []), to: Tokenizers.Native, as: :packing_pack
+end
@@ -11,6 +11,14 @@ defmodule Tokenizers.PostProcessor do
 
   @type t() :: %__MODULE__{resource: reference()}
 
+  @doc """
+  Computes a fingerprint of the post-processor configuration.
+
+  See `Tokenizers.Tokenizer.fingerprint/2`.
+  """
+  @spec fingerprint(t()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(post_processor), to: Tokenizers.Native, as: :post_processors_fingerprint
+
   @doc """
   Creates a Bert post-processor with the given tokens.
   """
@@ -74,9 +82,15 @@ defmodule Tokenizers.PostProcessor do
     * `:special_tokens` - a list of special tokens to use in the
       template. Must be a list of `{token, token_id}` tuples
 
+  Raises `Tokenizers.Error` if a template is malformed or uses special
+  tokens that are not given.
   """
   @spec template(keyword()) :: t()
-  defdelegate template(opts \\ []), to: Tokenizers.Native, as: :post_processors_template
+  def template(opts \\ []) do
+    opts
+    |> Tokenizers.Native.post_processors_template()
+    |> Tokenizers.Shared.unwrap()
+  end
 
   @doc """
   Instantiate a new Sequence post-processor
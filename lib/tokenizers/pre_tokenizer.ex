@@ -20,6 +20,14 @@ defmodule Tokenizers.PreTokenizer do
 
   @type t() :: %__MODULE__{resource: reference()}
 
+  @doc """
+  Computes a fingerprint of the pre-tokenizer configuration.
+
+  See `Tokenizers.Tokenizer.fingerprint/2`.
+  """
+  @spec fingerprint(t()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(pre_tokenizer), to: Tokenizers.Native, as: :pre_tokenizers_fingerprint
+
   @doc """
   Converts a string into a sequence of pre-tokens.
   """
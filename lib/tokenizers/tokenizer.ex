@@ -35,6 +35,79 @@ defmodule Tokenizers.Tokenizer do
   """
   @type encode_input :: String.t() | {String.t(), String.t()}
 
+  @typedoc """
+  Encodings with their overflowing windows flattened into a single list.
+
+  `:overflow_to_sample_mapping` holds the index of the input each window
+  comes from, and `:window_index` the position of the window within that
+  input, where `0` is the window starting at the beginning of the input.
+  """
+  @type flat_encodings :: %{
+          encodings: [Encoding.t()],
+          overflow_to_sample_mapping: [non_neg_integer()],
+          window_index: [non_neg_integer()]
+        }
+
+  @typedoc """
+  Flattened windows stacked into binaries of native u32 values.
+
+  The token binaries have `:shape`, that is `{windows, length}`, while
+  `:overflow_to_sample_mapping` and `:window_index` have one value per
+  window, as in `t:flat_encodings/0`.
+  """
+  @type flat_binaries :: %{
+          ids: binary(),
+          type_ids: binary(),
+          attention_mask: binary(),
+          special_tokens_mask: binary(),
+          overflow_to_sample_mapping: binary(),
+          window_index: binary(),
+          shape: {non_neg_integer(), non_neg_integer()}
+        }
+
+  @typedoc """
+  Inputs grouped into batches of similar length.
+
+  `:indices` holds the input indices in each batch, `:batch_lengths`
+  the length every batch is padded to and `:restore_order` the position
+  of each input in the flattened batches, so that results can be put
+  back in the original input order.
+  """
+  @type buckets :: %{
+          batches: [[Encoding.t()]] | nil,
+          indices: [[non_neg_integer()]],
+          batch_lengths: [non_neg_integer()],
+          restore_order: [non_neg_integer()]
+        }
+
+  @typedoc """
+  A conversation turn, as `{role, text}` or `{role, text, trainable}`.
+  """
+  @type chat_segment :: {String.t(), String.t()} | {String.t(), String.t(), boolean()}
+
+  @typedoc """
+  An encoded conversation.
+
+  `:loss_mask` is a binary of native u32 values with `1` for the tokens
+  that count towards the loss, and `:labels` a binary of native s32
+  values with the token ids where the mask is set and the ignore index
+  elsewhere. `:turns` lists the turns kept after truncation, with the
+  index of the original segment and the token range it covers.
+  """
+  @type chat_encoding :: %{
+          encoding: Encoding.t(),
+          loss_mask: binary(),
+          labels: binary(),
+          turns: [
+            %{
+              segment: non_neg_integer(),
+              role: String.t(),
+              start: non_neg_integer(),
+              end: non_neg_integer()
+            }
+          ]
+        }
+
   @doc """
   Loads a new tokenizer from a repository on Hugging Face Hub.
 
@@ -444,6 +517,33 @@ defmodule Tokenizers.Tokenizer do
     to: Tokenizers.Native,
     as: :tokenizer_disable_padding
 
+  @doc """
+  Sets whether special tokens in the input text are encoded as ordinary
+  text.
+
+  By default, text matching a special token, such as `"[SEP]"`, is
+  encoded as that special token. When enabled, the text is tokenized
+  like any other, so that user-supplied text cannot insert control
+  tokens. Special tokens added by the post-processor are not affected.
+  """
+  @doc type: :configuration
+  @spec set_encode_special_tokens(tokenizer :: t(), value :: boolean()) :: t()
+  defdelegate set_encode_special_tokens(tokenizer, value),
+    to: Tokenizers.Native,
+    as: :tokenizer_set_encode_special_tokens
+
+  @doc """
+  Returns whether special tokens in the input text are encoded as
+  ordinary text.
+
+  See `set_encode_special_tokens/2`.
+  """
+  @doc type: :configuration
+  @spec get_encode_special_tokens(tokenizer :: t()) :: boolean()
+  defdelegate get_encode_special_tokens(tokenizer),
+    to: Tokenizers.Native,
+    as: :tokenizer_get_encode_special_tokens
+
   @doc """
   Encode the given sequence to a `Tokenizers.Encoding.t()`.
 
@@ -456,22 +556,326 @@ defmodule Tokenizers.Tokenizer do
       to apply to the encoding. Check `Tokenizers.Encoding.transform/2`
       for more information. Defaults to `[]`
 
+    * `:overflowing` - how to return the windows produced by truncation
+      with a stride. Defaults to `:nested`. One of:
+
+        * `:nested` - the windows are available through
+          `Tokenizers.Encoding.get_overflowing/1`
+
+        * `:flat` - returns a `t:flat_encodings/0` map, with every window
+          as a separate encoding
+
+        * `:binaries` - returns a `t:flat_binaries/0` map, with the
+          windows stacked into u32 binaries. All windows must have the
+          same length, which usually means enabling padding
+
+    * `:encode_special_tokens` - whether special tokens in the input
+      text are encoded as ordinary text, see `set_encode_special_tokens/2`.
+      Either a boolean or a list with a boolean for each sequence of a
+      pair, such as `[false, true]`. Defaults to the tokenizer setting
+
+    * `:protected_spans` - a list of spans of the input that no token
+      crosses, applying only to this call. Each span is either:
+
+        * `{start, end}` - a range of UTF-8 byte offsets in the
+          sequence, encoded on its own
+
+        * `{start, end, id}` - a range of UTF-8 byte offsets in the
+          sequence, encoded as a single token with the given id
+
+        * `literal` - every occurrence of the string in the sequences,
+          encoded on its own
+
+        * `{literal, id}` - every occurrence of the string in the
+          sequences, encoded as a single token with the given id
+
+      Ranges are only supported when encoding a single sequence, and
+      character offsets are not supported, so ranges over non-ASCII
+      text must be converted to byte offsets first. Ids must be in the
+      vocabulary. The text between spans is encoded separately, with
+      offsets and word ids that remain relative to the whole sequence.
+      Spans must not overlap. Defaults to `[]`
+
+    * `:sampling` - samples the segmentation of every word instead of
+      using the best one, as in subword regularization. Only supported
+      with a Unigram model, and not together with `:protected_spans`.
+      A keyword list with:
+
+        * `:alpha` - the smoothing parameter, where lower values give
+          more varied segmentations. Defaults to `0.1`
+
+        * `:seed` - the random seed, making the result reproducible.
+          Defaults to a random seed
+
+    * `:dropout` - applies BPE dropout to this call, skipping every
+      merge with some probability. Only supported with a BPE model, and
+      not together with `:protected_spans`. Any dropout set on the model
+      itself is ignored. A keyword list with:
+
+        * `:probability` - the probability of skipping a merge. Defaults
+          to `0.1`
+
+        * `:seed` - the random seed, making the result reproducible.
+          Defaults to a random seed
+
   """
   @doc type: :inference
-  @spec encode(t(), encode_input(), keyword()) :: {:ok, Encoding.t()} | {:error, term()}
+  @spec encode(t(), encode_input(), keyword()) ::
+          {:ok, Encoding.t() | flat_encodings() | flat_binaries()} | {:error, term()}
   defdelegate encode(tokenizer, input, opts \\ []),
     to: Tokenizers.Native,
     as: :tokenizer_encode
 
   @doc """
   Batched version of `encode/3`.
+
+  With the `:overflowing` option set to `:flat` or `:binaries`, the
+  windows of all inputs are flattened into a single batch, and
+  `:overflow_to_sample_mapping` gives the index of the input each
+  window comes from.
+
+  Only literal `:protected_spans` are supported, and they apply to all
+  the inputs. With `:sampling` or `:dropout`, every input gets its own
+  random stream derived from the seed, so its result does not depend on
+  the other inputs of the batch.
   """
   @doc type: :inference
-  @spec encode_batch(t(), [encode_input()], keyword()) :: {:ok, [Encoding.t()]} | {:error, term()}
+  @spec encode_batch(t(), [encode_input()], keyword()) ::
+          {:ok, [Encoding.t()] | flat_encodings() | flat_binaries()} | {:error, term()}
   defdelegate encode_batch(tokenizer, input, opts \\ []),
     to: Tokenizers.Native,
     as: :tokenizer_encode_batch
 
+  @doc """
+  Encodes the input with each of the `n` best segmentations.
+
+  Only supported with a Unigram model. Returns up to `n` encodings, best
+  first, each with its score, the log-probability of the segmentation.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add special tokens to the
+      sequence. Defaults to `true`
+
+    * `:encode_special_tokens` - whether special tokens in the input
+      text are encoded as ordinary text, as in `encode/3`. Defaults to
+      the tokenizer setting
+
+  """
+  @doc type: :inference
+  @spec encode_nbest(t(), encode_input(), pos_integer(), keyword()) ::
+          {:ok, [%{encoding: Encoding.t(), score: float()}]} | {:error, term()}
+  defdelegate encode_nbest(tokenizer, input, n, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_encode_nbest
+
+  @doc """
+  Encodes the inputs and groups them into batches of similar length.
+
+  Inputs are sorted by their number of tokens and split into batches,
+  so that each batch padded to its longest input holds at most
+  `max_tokens` tokens. An input longer than `max_tokens` on its own
+  gets a batch of its own. Each batch is padded separately, using the
+  tokenizer padding parameters (pad token, id and direction) when set.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add special tokens to the
+      sequences. Defaults to `true`
+
+    * `:max_batch_size` - the maximum number of inputs in a batch.
+      Defaults to no limit
+
+    * `:pad_to_multiple_of` - pads every batch to a multiple of this
+      length. Defaults to the tokenizer padding configuration
+
+    * `:return_encodings` - whether to return the padded encodings in
+      `:batches`. When `false`, only the grouping is computed and
+      `:batches` is `nil`. Defaults to `true`
+
+  """
+  @doc type: :inference
+  @spec encode_bucketed(t(), [encode_input()], pos_integer(), keyword()) ::
+          {:ok, buckets()} | {:error, term()}
+  defdelegate encode_bucketed(tokenizer, inputs, max_tokens, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_encode_bucketed
+
+  @doc """
+  Encodes a conversation for supervised fine-tuning.
+
+  Each segment is encoded on its own and wrapped with the template of
+  its role, then the turns are concatenated and post-processed as a
+  single sequence. Only the text and template suffix of trainable
+  turns count towards the loss, while the template prefix, the special
+  tokens added by the post-processor and padding never do.
+
+  When the conversation is longer than `:max_length`, whole turns are
+  dropped starting from the earliest one, skipping the first
+  `:keep_first` turns. The last turn is never dropped, and is truncated
+  instead when it does not fit on its own. Offsets are relative to the
+  text of the kept turns, each wrapped with its template, concatenated
+  together, and word ids are numbered across the whole conversation.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add the post-processor special
+      tokens around the conversation. Defaults to `true`
+
+    * `:trainable_roles` - the roles of the `{role, text}` segments that
+      count towards the loss. Defaults to `["assistant"]`
+
+    * `:templates` - a map from role to a `{prefix, suffix}` tuple of
+      strings encoded around the text of each turn, for example
+      `%{"user" => {"<|user|>", "<|end|>"}}`. Special tokens in the
+      templates are recognized. Defaults to `%{}`
+
+    * `:max_length` - the maximum length of the encoding, including
+      special tokens. Defaults to the tokenizer truncation max length
+
+    * `:keep_first` - the number of leading turns that are never
+      dropped, such as a system prompt. Defaults to `0`
+
+    * `:ignore_index` - the label of the tokens that do not count
+      towards the loss. Defaults to `-100`
+
+    * `:encode_special_tokens` - whether special tokens in the text of
+      the segments are encoded as ordinary text, see
+      `set_encode_special_tokens/2`. Either a boolean or a list of the
+      roles to apply it to, such as `["user"]`. Templates always encode
+      special tokens. Defaults to the tokenizer setting
+
+  """
+  @doc type: :inference
+  @spec encode_chat(t(), [chat_segment()], keyword()) ::
+          {:ok, chat_encoding()} | {:error, term()}
+  defdelegate encode_chat(tokenizer, segments, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_encode_chat
+
+  @doc """
+  Encodes any number of sequences into a single encoding.
+
+  The sequences are combined according to a template, in the same
+  syntax as `Tokenizers.PostProcessor.template/1`, extended to the
+  sequences `$A` to `$Z`. Each piece can be given a type id with a
+  `:type_id` suffix, as in `"[CLS] $A [SEP] $B:1 [SEP]:1 $C:2 [SEP]:2"`.
+  By default, every sequence gets its index as type id and special
+  tokens take the type id of the preceding sequence. The sequence ids
+  of the encoding are the indices of the sequences.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add the special tokens from
+      the template. Defaults to `true`
+
+    * `:template` - the template combining the sequences. Special
+      tokens must be in the tokenizer vocabulary. Defaults to the
+      sequences one after the other, without special tokens
+
+    * `:max_length` - the maximum length of the encoding, including
+      special tokens. Defaults to the tokenizer truncation max length
+
+    * `:budgets` - a list with the maximum length of each sequence,
+      where `nil` means no limit. Defaults to `[]`
+
+    * `:priorities` - a list with the priority of each sequence, where
+      missing values are `0`. To fit `:max_length`, sequences with the
+      lowest priority are truncated first and, among sequences with the
+      same priority, the last ones are. Defaults to `[]`
+
+    * `:direction` - the direction to truncate sequences from, either
+      `:left` or `:right`. Defaults to the tokenizer truncation
+      direction or `:right`
+
+    * `:encode_special_tokens` - whether special tokens in the
+      sequences are encoded as ordinary text, see
+      `set_encode_special_tokens/2`. Either a boolean or a list with a
+      boolean for each sequence, where missing values are `false`.
+      Special tokens from the template are not affected. Defaults to the
+      tokenizer setting
+
+  """
+  @doc type: :inference
+  @spec encode_multi(t(), [String.t()], keyword()) :: {:ok, Encoding.t()} | {:error, term()}
+  defdelegate encode_multi(tokenizer, sequences, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_encode_multi
+
+  @doc """
+  Encodes the input, expanding placeholder tokens into runs of tokens.
+
+  This is useful for vision-language models, where a single placeholder,
+  such as `"<image>"`, stands for the tokens of an image, whose number
+  varies per image. `placeholders` is a map from placeholder token to
+  its expansion, which is either:
+
+    * an integer, repeating every occurrence of the placeholder that
+      many times
+
+    * a list with the expansion of each occurrence, in order, where
+      each element is either a number of repetitions or a list of ids
+      to replace the placeholder with
+
+  Placeholders must be tokens of the tokenizer, usually added with
+  `add_special_tokens/2`. Expansion happens before truncation,
+  post-processing and padding, so the expanded tokens are counted
+  towards the max length. All the expanded tokens keep the offsets and
+  word id of their placeholder, and `:media_mask` is a binary of native
+  u32 values with `1` for every expanded token.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add special tokens to the
+      sequence. Defaults to `true`
+
+  """
+  @doc type: :inference
+  @spec encode_with_placeholders(
+          t(),
+          encode_input(),
+          %{String.t() => non_neg_integer() | [non_neg_integer() | [non_neg_integer()]]},
+          keyword()
+        ) :: {:ok, %{encoding: Encoding.t(), media_mask: binary()}} | {:error, term()}
+  defdelegate encode_with_placeholders(tokenizer, input, placeholders, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_encode_with_placeholders
+
+  @doc """
+  Runs the tokenizer pipeline on the input and returns the result of
+  every stage.
+
+  This is meant for debugging encodings. For each sequence of the input,
+  the trace has:
+
+    * `:added_tokens` - the splits of the input around added tokens,
+      where `:added_token` is the id of the matched token or `nil`
+
+    * `:normalized` - the normalized string, and `:alignments` with the
+      range of the original string each byte of the normalized string
+      comes from. Bytes of added tokens map to the whole token
+
+    * `:pre_tokens` - the splits produced by the pre-tokenizer
+
+    * `:model` - the tokens the model produced for every split
+
+  Splits have the normalized `:text`, and their `:original` and
+  `:normalized` byte offsets. Then, `:post_processor` lists the tokens
+  that do not come from any sequence, such as the ones added by the
+  post-processor or padding, and `:encoding` is the final encoding.
+
+  ## Options
+
+    * `:add_special_tokens` - whether to add special tokens to the
+      sequence. Defaults to `true`
+
+  """
+  @doc type: :inference
+  @spec trace(t(), encode_input(), keyword()) :: {:ok, map()} | {:error, term()}
+  defdelegate trace(tokenizer, input, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_trace
+
   @doc """
   Decodes the given list of ids back to a string.
 
@@ -515,6 +919,172 @@ defmodule Tokenizers.Tokenizer do
     to: Tokenizers.Native,
     as: :tokenizer_token_to_id
 
+  @typedoc """
+  The differences between two tokenizers.
+
+    * `:equal` - whether no difference was found
+
+    * `:config` - the differing options of the pipeline components and
+      model, padding and truncation, each with its `:path`, such as
+      `"normalizer.lowercase"`, and the `:left` and `:right` values
+      encoded as JSON, or `nil` when missing
+
+    * `:vocab` - the tokens `:added`, `:removed` and whose id `:changed`,
+      added tokens included
+
+    * `:merges` - the BPE merges `:added` and `:removed`, and whether the
+      merges of both sides are `:reordered`
+
+    * `:scores` - the Unigram pieces whose score changed
+
+    * `:added_tokens` - the added tokens `:added`, `:removed` and whose
+      options `:changed`
+
+    * `:samples` - the inputs of the corpus encoded to different ids
+
+  """
+  @type diff() :: %{
+          equal: boolean(),
+          config: [%{path: String.t(), left: String.t() | nil, right: String.t() | nil}],
+          vocab: %{
+            added: [{String.t(), non_neg_integer()}],
+            removed: [{String.t(), non_neg_integer()}],
+            changed: [{String.t(), non_neg_integer(), non_neg_integer()}]
+          },
+          merges: %{
+            added: [{String.t(), String.t()}],
+            removed: [{String.t(), String.t()}],
+            reordered: boolean()
+          },
+          scores: [{String.t(), float(), float()}],
+          added_tokens: %{
+            added: [String.t()],
+            removed: [String.t()],
+            changed: [
+              %{
+                content: String.t(),
+                flag: :special | :single_word | :lstrip | :rstrip | :normalized,
+                left: boolean(),
+                right: boolean()
+              }
+            ]
+          },
+          samples: [
+            %{
+              index: non_neg_integer(),
+              input: String.t(),
+              left: [non_neg_integer()],
+              right: [non_neg_integer()]
+            }
+          ]
+        }
+
+  @doc """
+  Compares two tokenizers, for example to check whether two versions of
+  a `tokenizer.json` file are equivalent.
+
+  The configuration of both tokenizers is compared structurally, along
+  with their vocabularies and added tokens. Optionally, a sample corpus
+  is encoded with both tokenizers to find inputs encoded differently.
+
+  ## Options
+
+    * `:corpus` - a list of inputs to encode with both tokenizers.
+      Padding is disabled, while truncation applies. Defaults to `[]`
+
+    * `:max_samples` - the maximum number of differing inputs to report.
+      Defaults to `10`
+
+    * `:add_special_tokens` - whether to add special tokens when
+      encoding the corpus. Defaults to `true`
+
+  """
+  @doc type: :inspection
+  @spec diff(t(), t(), keyword()) :: {:ok, diff()} | {:error, term()}
+  defdelegate diff(left, right, opts \\ []), to: Tokenizers.Native, as: :tokenizer_diff
+
+  @typedoc """
+  An inconsistency found in the tokenizer configuration.
+
+    * `:severity` - `:error` when encoding or decoding is broken, such
+      as a special token with a wrong id, and `:warning` when it is
+      likely unintended, such as a decoder not reversing the
+      pre-tokenizer
+
+    * `:rule` - the check that failed
+
+    * `:path` - the component the issue was found in, such as
+      `"padding.pad_id"` or `"decoder.replacement"`
+
+    * `:message` - a human-readable description
+
+  """
+  @type validation_issue() :: %{
+          severity: :error | :warning,
+          rule:
+            :unknown_special_token
+            | :special_token_id_mismatch
+            | :special_token_not_special
+            | :unknown_pad_token
+            | :pad_id_mismatch
+            | :unknown_unk_token
+            | :unk_id_out_of_range
+            | :byte_level_mismatch
+            | :metaspace_mismatch
+            | :word_piece_prefix_mismatch
+            | :truncation_too_short,
+          path: String.t(),
+          message: String.t()
+        }
+
+  @doc """
+  Checks the tokenizer for inconsistent configurations.
+
+  The components of a tokenizer are built independently, so nothing
+  prevents, for example, a post-processor from referencing a special
+  token missing from the vocabulary, or a pre-tokenizer from being
+  paired with a decoder that does not reverse it. Such tokenizers load
+  fine, but produce wrong encodings or decodings.
+
+  Returns the list of issues found, empty when the tokenizer is
+  consistent.
+  """
+  @doc type: :inspection
+  @spec validate(t()) :: {:ok, [validation_issue()]} | {:error, term()}
+  defdelegate validate(tokenizer), to: Tokenizers.Native, as: :tokenizer_validate
+
+  @doc """
+  Computes a fingerprint of the tokenizer, for example to key caches of
+  encoded data.
+
+  The fingerprint is a hash of the whole tokenizer state: the model
+  with its vocabulary, the pipeline components, added tokens, padding
+  and truncation. Unlike a hash of the `tokenizer.json` file, it does
+  not depend on key ordering or formatting.
+
+  The fingerprint is computed from the serialization of the underlying
+  tokenizers library, so it may change when that serialization changes,
+  such as when upgrading this package. Fingerprints are meant to be
+  compared within the same version.
+
+  The components have their own fingerprint function, such as
+  `Tokenizers.Model.fingerprint/1`.
+
+  ## Options
+
+    * `:exclude` - components to leave out of the fingerprint, among
+      `:normalizer`, `:pre_tokenizer`, `:model`, `:post_processor`,
+      `:decoder`, `:added_tokens`, `:padding` and `:truncation`. For
+      example, excluding `:decoder` gives a fingerprint that only
+      changes when encoding could. Defaults to `[]`
+
+  """
+  @doc type: :inspection
+  @spec fingerprint(t(), keyword()) :: {:ok, String.t()} | {:error, term()}
+  defdelegate fingerprint(tokenizer, opts \\ []),
+    to: Tokenizers.Native,
+    as: :tokenizer_fingerprint
+
   @doc """
   Train the tokenizer on the given files.
 
@@ -0,0 +1,170 @@
+defmodule Tokenizers.Vocabulary do
+  @moduledoc """
+  Functions to edit the vocabulary of a tokenizer.
+
+  The token ids of a tokenizer index into the embedding matrix of its
+  model, so these functions return enough information to update the
+  embeddings to match.
+  """
+
+  @typedoc """
+  The result of pruning a vocabulary.
+
+    * `:tokenizer` - the tokenizer with the pruned vocabulary
+
+    * `:new_ids` - native s32 values indexed by old id, with the new
+      id of every token, or `-1` for removed tokens
+
+    * `:old_ids` - native u32 values indexed by new id, with the old
+      id of every token. Taking these rows of the embedding matrix
+      gives the embeddings of the pruned vocabulary
+
+  """
+  @type pruned() :: %{
+          tokenizer: Tokenizers.Tokenizer.t(),
+          new_ids: binary(),
+          old_ids: binary()
+        }
+
+  @doc """
+  Removes the tokens that `corpus` does not use from the vocabulary.
+
+  The corpus is encoded with `tokenizer`, without truncation and padding,
+  and the model is rebuilt with only the tokens that appear. The model
+  keeps the order of its tokens, so new ids follow the order of the old
+  ones, and added tokens come after the model vocabulary as usual.
+
+  Besides the tokens of the corpus, the pruned vocabulary always keeps
+  the added tokens, the special tokens of the post-processor, the pad
+  token and the unknown token of the model. For BPE models, the tokens
+  merged into a kept token and the merges between kept tokens are kept
+  as well, so the corpus is tokenized exactly the same way. Unigram
+  models keep the scores of their tokens, and every model keeps its
+  settings.
+
+  Supports BPE, WordPiece, WordLevel and Unigram models.
+
+  ## Options
+
+    * `:keep` - a list of tokens to keep even if the corpus does not
+      use them. Defaults to `[]`
+
+    * `:alphabet` - a list of chars whose tokens are kept, so that
+      any text made of them can still be tokenized. This includes the
+      tokens with the continuing subword prefix or end of word suffix
+      of the model. Defaults to `[]`
+
+    * `:keep_bytes` - whether to keep the tokens of every byte, so
+      that any text can still be tokenized. These are the byte fallback
+      tokens, from `<0x00>` to `<0xFF>`, and, when the normalizer,
+      pre-tokenizer or decoder is byte-level, the tokens of every char
+      of `Tokenizers.PreTokenizer.byte_level_alphabet/0`. Defaults to
+      `true`
+
+  """
+  @spec prune(Tokenizers.Tokenizer.t(), [String.t()], keyword()) ::
+          {:ok, pruned()} | {:error, term()}
+  defdelegate prune(tokenizer, corpus, opts \\ []), to: Tokenizers.Native, as: :vocabulary_prune
+
+  @typedoc """
+  The result of extending a vocabulary.
+
+    * `:tokenizer` - the tokenizer with the extended vocabulary
+
+    * `:tokens` - the new tokens with their id, in the order they
+      were learned
+
+  """
+  @type extended() :: %{
+          tokenizer: Tokenizers.Tokenizer.t(),
+          tokens: [{String.t(), non_neg_integer()}]
+        }
+
+  @doc """
+  Extends the model vocabulary with up to `size` tokens learned from
+  `corpus`.
+
+  The corpus is encoded with `tokenizer`, and the most frequent pairs of
+  adjacent tokens within a word are merged into new tokens, one after
+  another, the way BPE training does. Unlike
+  `Tokenizers.Tokenizer.add_tokens/2`, the new tokens belong to the
+  model, so they go through normalization and pre-tokenization like
+  any other token.
+
+  Existing ids are preserved and new tokens are appended after the
+  highest id, so only the embeddings of new tokens need initialising.
+  Added tokens that are not part of the model are moved into it to keep
+  their ids.
+
+  Supports the following models:
+
+    * BPE - a merge is appended for every new token
+
+    * WordPiece - the continuing subword prefix of the model is
+      respected when merging
+
+    * Unigram - every new token is scored by its frequency among the
+      tokens of the corpus, once merged, clamped to the range of the
+      scores of the model, leaving out the unknown token
+
+  Pairs that would merge into a token already in the vocabulary are
+  skipped, as well as unknown, byte fallback and added tokens.
+
+  ## Options
+
+    * `:min_frequency` - the minimum number of occurrences of a pair
+      for it to be merged. Defaults to `2`
+
+    * `:max_token_length` - the maximum length of new tokens, in
+      chars. Defaults to `nil`, meaning no limit
+
+  """
+  @spec extend(Tokenizers.Tokenizer.t(), [String.t()], non_neg_integer(), keyword()) ::
+          {:ok, extended()} | {:error, term()}
+  defdelegate extend(tokenizer, corpus, size, opts \\ []),
+    to: Tokenizers.Native,
+    as: :vocabulary_extend
+
+  @doc """
+  Renames the token with the given `id`, keeping its id.
+
+  The token is renamed in the model vocabulary and in the added tokens,
+  wherever it is part of them, as well as in the post-processor and
+  padding configuration. The new content must not be in the vocabulary
+  already. BPE tokens that are part of a merge cannot be renamed.
+
+  To repurpose a token as a control token, such as the reserved special
+  tokens of some models, rename it and mark it as special with
+  `update_added_token/3`.
+  """
+  @spec rename(Tokenizers.Tokenizer.t(), non_neg_integer(), String.t()) ::
+          {:ok, Tokenizers.Tokenizer.t()} | {:error, term()}
+  defdelegate rename(tokenizer, id, content), to: Tokenizers.Native, as: :vocabulary_rename
+
+  @doc """
+  Updates the options of the added token with the given `id`.
+
+  Accepts the same options as `Tokenizers.AddedToken.new/2`, and only
+  changes the given ones. A token of the model vocabulary that is not an
+  added token yet becomes one.
+  """
+  @spec update_added_token(Tokenizers.Tokenizer.t(), non_neg_integer(), keyword()) ::
+          {:ok, Tokenizers.Tokenizer.t()} | {:error, term()}
+  defdelegate update_added_token(tokenizer, id, opts),
+    to: Tokenizers.Native,
+    as: :vocabulary_update_added_token
+
+  @doc """
+  Removes the added tokens with the given `ids`.
+
+  Tokens that are also part of the model vocabulary stay in it, as
+  ordinary tokens. Other tokens are removed from the vocabulary, which
+  is only possible when no remaining added token comes after them, since
+  ids would be shifted otherwise.
+  """
+  @spec remove_added_tokens(Tokenizers.Tokenizer.t(), [non_neg_integer()]) ::
+          {:ok, Tokenizers.Tokenizer.t()} | {:error, term()}
+  defdelegate remove_added_tokens(tokenizer, ids),
+    to: Tokenizers.Native,
+    as: :vocabulary_remove_added_tokens
+end
@@ -47,6 +47,7 @@ defmodule Tokenizers.MixProject do
           Tokenizers.Tokenizer,
           Tokenizers.Encoding,
           Tokenizers.Encoding.Transformation,
+          Tokenizers.Packing,
           Tokenizers.Decoder
         ],
         Pipeline: [
@@ -61,9 +62,11 @@ defmodule Tokenizers.MixProject do
           Tokenizers.Model.WordLevel,
           Tokenizers.Model.WordPiece,
           Tokenizers.Trainer,
-          Tokenizers.AddedToken
+          Tokenizers.AddedToken,
+          Tokenizers.Vocabulary
         ],
         Other: [
+          Tokenizers.Error,
           Tokenizers.HTTPClient
         ]
       ],
@@ -72,6 +75,7 @@ defmodule Tokenizers.MixProject do
         Loading: &(&1[:type] == :loading),
         Inference: &(&1[:type] == :inference),
         Configuration: &(&1[:type] == :configuration),
+        Inspection: &(&1[:type] == :inspection),
         Training: &(&1[:type] == :training)
       ]
     ]
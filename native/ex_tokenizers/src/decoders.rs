@@ -2,6 +2,7 @@ use rustler::NifTaggedEnum;
 use serde::{Deserialize, Serialize};
 use tokenizers::{Decoder, DecoderWrapper};
 
+use crate::util::fingerprint;
 use crate::{new_info, util::Info, ExTokenizersError};
 
 pub struct ExTokenizersDecoderRef(pub DecoderWrapper);
@@ -138,6 +139,11 @@ fn decoders_info(decoder: ExTokenizersDecoder) -> Info {
     }
 }
 
+#[rustler::nif]
+fn decoders_fingerprint(decoder: ExTokenizersDecoder) -> Result<String, ExTokenizersError> {
+    fingerprint(&decoder)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// Builders
 ///////////////////////////////////////////////////////////////////////////////
@@ -151,10 +157,10 @@ fn decoders_byte_level() -> ExTokenizersDecoder {
 fn decoders_replace(
     pattern: String,
     content: String,
-) -> Result<ExTokenizersDecoder, rustler::Error> {
+) -> Result<ExTokenizersDecoder, ExTokenizersError> {
     Ok(ExTokenizersDecoder::new(
         tokenizers::normalizers::Replace::new(pattern, content)
-            .map_err(|_| rustler::Error::BadArg)?,
+            .map_err(|error| ExTokenizersError::InvalidPattern(error.to_string()))?,
     ))
 }
 
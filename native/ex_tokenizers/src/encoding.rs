@@ -1,6 +1,9 @@
+use std::collections::{HashMap, HashSet};
+
 use rustler::{Binary, Env, NifTaggedEnum, ResourceArc};
 use tokenizers::Encoding;
 
+use crate::error::ExTokenizersError;
 use crate::util::Direction;
 
 pub struct ExTokenizersEncodingRef(pub Encoding);
@@ -278,6 +281,13 @@ fn slice_u32_to_u8(slice: &[u32]) -> &[u8] {
     unsafe { std::slice::from_raw_parts(slice.as_ptr() as *const u8, slice.len() * 4) }
 }
 
+fn slice_u8_to_f32(slice: &[u8]) -> Vec<f32> {
+    slice
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// Encoding transformations
 ///////////////////////////////////////////////////////////////////////////////
@@ -324,3 +334,587 @@ pub fn apply_transformations(
         }
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+/// Entity aggregation
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(rustler::NifUnitEnum, Clone, Copy, PartialEq)]
+pub enum AggregationStrategy {
+    Simple,
+    First,
+    Average,
+    Max,
+}
+
+#[derive(rustler::NifUnitEnum, Clone, Copy, PartialEq)]
+pub enum PredictionsType {
+    Scores,
+    LabelIds,
+}
+
+#[derive(NifTaggedEnum)]
+pub enum AggregationOption {
+    AggregationStrategy(AggregationStrategy),
+    PredictionsType(PredictionsType),
+    Softmax(bool),
+    IgnoreLabels(Vec<String>),
+    SequenceId(usize),
+}
+
+#[derive(rustler::NifMap)]
+pub struct Entity {
+    label: String,
+    score: f32,
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+struct TokenPrediction {
+    word: u32,
+    offsets: (usize, usize),
+    scores: Vec<f32>,
+    windows: usize,
+}
+
+struct WordPrediction {
+    label: usize,
+    score: f32,
+    offsets: (usize, usize),
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn encoding_aggregate_entities<'a>(
+    encodings: Vec<ExTokenizersEncoding>,
+    text: String,
+    predictions: Binary<'a>,
+    labels: Vec<String>,
+    options: Vec<AggregationOption>,
+) -> Result<Vec<Entity>, ExTokenizersError> {
+    struct Opts {
+        aggregation_strategy: AggregationStrategy,
+        predictions_type: PredictionsType,
+        softmax: bool,
+        ignore_labels: Vec<String>,
+        sequence_id: usize,
+    }
+    let mut opts = Opts {
+        aggregation_strategy: AggregationStrategy::First,
+        predictions_type: PredictionsType::Scores,
+        softmax: true,
+        ignore_labels: vec!["O".to_string()],
+        sequence_id: 0,
+    };
+    for option in options {
+        match option {
+            AggregationOption::AggregationStrategy(strategy) => {
+                opts.aggregation_strategy = strategy
+            }
+            AggregationOption::PredictionsType(predictions_type) => {
+                opts.predictions_type = predictions_type
+            }
+            AggregationOption::Softmax(softmax) => opts.softmax = softmax,
+            AggregationOption::IgnoreLabels(ignore_labels) => opts.ignore_labels = ignore_labels,
+            AggregationOption::SequenceId(sequence_id) => opts.sequence_id = sequence_id,
+        }
+    }
+
+    let n_rows: usize = encodings.iter().map(|e| e.resource.0.len()).sum();
+    let rows = decode_predictions(
+        predictions.as_slice(),
+        n_rows,
+        labels.len(),
+        opts.predictions_type,
+        opts.softmax,
+    )?;
+
+    // Tokens in the stride of overflowing windows show up more than once,
+    // so their scores are averaged across windows.
+    let mut tokens: Vec<TokenPrediction> = Vec::new();
+    let mut seen: HashMap<(u32, (usize, usize)), usize> = HashMap::new();
+    let mut row = 0;
+    for encoding in encodings.iter() {
+        let encoding = &encoding.resource.0;
+        for (i, sequence_id) in encoding.get_sequence_ids().into_iter().enumerate() {
+            let scores = &rows[row];
+            row += 1;
+
+            if encoding.get_special_tokens_mask()[i] == 1
+                || encoding.get_attention_mask()[i] == 0
+                || sequence_id != Some(opts.sequence_id)
+            {
+                continue;
+            }
+            let Some(word) = encoding.get_word_ids()[i] else {
+                continue;
+            };
+            let offsets = encoding.get_offsets()[i];
+
+            match seen.get(&(word, offsets)) {
+                Some(&index) => {
+                    let token = &mut tokens[index];
+                    token
+                        .scores
+                        .iter_mut()
+                        .zip(scores)
+                        .for_each(|(acc, score)| *acc += score);
+                    token.windows += 1;
+                }
+                None => {
+                    seen.insert((word, offsets), tokens.len());
+                    tokens.push(TokenPrediction {
+                        word,
+                        offsets,
+                        scores: scores.clone(),
+                        windows: 1,
+                    });
+                }
+            }
+        }
+    }
+    for token in tokens.iter_mut() {
+        let windows = token.windows as f32;
+        token.scores.iter_mut().for_each(|score| *score /= windows);
+    }
+    tokens.sort_by_key(|token| token.offsets);
+
+    let words = aggregate_words(&tokens, opts.aggregation_strategy);
+    Ok(group_entities(words, &labels, &opts.ignore_labels, &text))
+}
+
+fn decode_predictions(
+    predictions: &[u8],
+    n_rows: usize,
+    n_labels: usize,
+    predictions_type: PredictionsType,
+    softmax: bool,
+) -> Result<Vec<Vec<f32>>, ExTokenizersError> {
+    match predictions_type {
+        PredictionsType::Scores => {
+            if n_labels == 0 || predictions.len() != n_rows * n_labels * 4 {
+                return Err(ExTokenizersError::Other(format!(
+                    "expected predictions of {} f32 scores ({} tokens x {} labels), got {} bytes",
+                    n_rows * n_labels,
+                    n_rows,
+                    n_labels,
+                    predictions.len()
+                )));
+            }
+            Ok(predictions
+                .chunks_exact(n_labels * 4)
+                .map(|row| {
+                    let mut scores = slice_u8_to_f32(row);
+                    if softmax {
+                        let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                        scores.iter_mut().for_each(|s| *s = (*s - max).exp());
+                        let sum: f32 = scores.iter().sum();
+                        scores.iter_mut().for_each(|s| *s /= sum);
+                    }
+                    scores
+                })
+                .collect())
+        }
+        PredictionsType::LabelIds => {
+            if predictions.len() != n_rows * 4 {
+                return Err(ExTokenizersError::Other(format!(
+                    "expected predictions of {} u32 label ids, got {} bytes",
+                    n_rows,
+                    predictions.len()
+                )));
+            }
+            predictions
+                .chunks_exact(4)
+                .map(|bytes| {
+                    let label = u32::from_ne_bytes(bytes.try_into().unwrap()) as usize;
+                    if label >= n_labels {
+                        return Err(ExTokenizersError::Other(format!(
+                            "label id {label} is out of range for {n_labels} labels"
+                        )));
+                    }
+                    let mut scores = vec![0.0; n_labels];
+                    scores[label] = 1.0;
+                    Ok(scores)
+                })
+                .collect()
+        }
+    }
+}
+
+fn argmax(scores: &[f32]) -> (usize, f32) {
+    scores
+        .iter()
+        .cloned()
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (label, score)| {
+            if score > best.1 {
+                (label, score)
+            } else {
+                best
+            }
+        })
+}
+
+fn aggregate_words(
+    tokens: &[TokenPrediction],
+    strategy: AggregationStrategy,
+) -> Vec<WordPrediction> {
+    if strategy == AggregationStrategy::Simple {
+        return tokens
+            .iter()
+            .map(|token| {
+                let (label, score) = argmax(&token.scores);
+                WordPrediction {
+                    label,
+                    score,
+                    offsets: token.offsets,
+                }
+            })
+            .collect();
+    }
+
+    tokens
+        .chunk_by(|a, b| a.word == b.word)
+        .map(|word| {
+            let offsets = (word[0].offsets.0, word[word.len() - 1].offsets.1);
+            let (label, score) = match strategy {
+                AggregationStrategy::First | AggregationStrategy::Simple => argmax(&word[0].scores),
+                AggregationStrategy::Average => {
+                    let mut scores = vec![0.0; word[0].scores.len()];
+                    for token in word {
+                        scores
+                            .iter_mut()
+                            .zip(&token.scores)
+                            .for_each(|(acc, score)| *acc += score);
+                    }
+                    scores.iter_mut().for_each(|s| *s /= word.len() as f32);
+                    argmax(&scores)
+                }
+                AggregationStrategy::Max => word.iter().map(|token| argmax(&token.scores)).fold(
+                    (0, f32::NEG_INFINITY),
+                    |best, candidate| {
+                        if candidate.1 > best.1 {
+                            candidate
+                        } else {
+                            best
+                        }
+                    },
+                ),
+            };
+            WordPrediction {
+                label,
+                score,
+                offsets,
+            }
+        })
+        .collect()
+}
+
+fn group_entities(
+    words: Vec<WordPrediction>,
+    labels: &[String],
+    ignore_labels: &[String],
+    text: &str,
+) -> Vec<Entity> {
+    struct Group<'l> {
+        label: &'l str,
+        scores: Vec<f32>,
+        start: usize,
+        end: usize,
+    }
+
+    let to_entity = |group: Group| {
+        let text = text
+            .as_bytes()
+            .get(group.start..group.end)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+            .unwrap_or_default();
+        Entity {
+            label: group.label.to_string(),
+            score: group.scores.iter().sum::<f32>() / group.scores.len() as f32,
+            start: group.start,
+            end: group.end,
+            text,
+        }
+    };
+
+    let mut entities = Vec::new();
+    let mut current: Option<Group> = None;
+    for word in words {
+        let label = labels[word.label].as_str();
+        if ignore_labels.iter().any(|ignored| ignored == label) {
+            entities.extend(current.take().map(to_entity));
+            continue;
+        }
+
+        let (begins, label) = match label.split_once('-') {
+            Some(("B", label)) => (true, label),
+            Some(("I", label)) => (false, label),
+            _ => (false, label),
+        };
+
+        match current.as_mut() {
+            Some(group) if !begins && group.label == label => {
+                group.scores.push(word.score);
+                group.end = word.offsets.1;
+            }
+            _ => {
+                entities.extend(current.take().map(to_entity));
+                current = Some(Group {
+                    label,
+                    scores: vec![word.score],
+                    start: word.offsets.0,
+                    end: word.offsets.1,
+                });
+            }
+        }
+    }
+    entities.extend(current.take().map(to_entity));
+    entities
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Question answering
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(NifTaggedEnum)]
+pub enum AnswerPositionsOption {
+    ContextSequenceId(usize),
+    ImpossibleIndex(usize),
+}
+
+#[derive(rustler::NifMap, Clone)]
+pub struct AnswerPositions {
+    start: usize,
+    end: usize,
+    impossible: bool,
+}
+
+fn context_range(encoding: &Encoding, sequence_id: usize) -> Option<(usize, usize)> {
+    let sequence_ids = encoding.get_sequence_ids();
+    let first = sequence_ids
+        .iter()
+        .position(|id| *id == Some(sequence_id))?;
+    let last = sequence_ids
+        .iter()
+        .rposition(|id| *id == Some(sequence_id))?;
+    Some((first, last))
+}
+
+#[rustler::nif]
+pub fn encoding_answer_token_positions(
+    encodings: Vec<ExTokenizersEncoding>,
+    answer: (usize, usize),
+    options: Vec<AnswerPositionsOption>,
+) -> Vec<AnswerPositions> {
+    struct Opts {
+        context_sequence_id: usize,
+        impossible_index: usize,
+    }
+    let mut opts = Opts {
+        context_sequence_id: 1,
+        impossible_index: 0,
+    };
+    for option in options {
+        match option {
+            AnswerPositionsOption::ContextSequenceId(id) => opts.context_sequence_id = id,
+            AnswerPositionsOption::ImpossibleIndex(index) => opts.impossible_index = index,
+        }
+    }
+
+    let (start_char, end_char) = answer;
+    let impossible = AnswerPositions {
+        start: opts.impossible_index,
+        end: opts.impossible_index,
+        impossible: true,
+    };
+
+    encodings
+        .iter()
+        .map(|encoding| {
+            let encoding = &encoding.resource.0;
+            let offsets = encoding.get_offsets();
+            let Some((context_start, context_end)) =
+                context_range(encoding, opts.context_sequence_id)
+            else {
+                return impossible.clone();
+            };
+
+            // The answer must be fully contained in this window's context
+            if offsets[context_start].0 > start_char || offsets[context_end].1 < end_char {
+                return impossible.clone();
+            }
+
+            let start = (context_start..=context_end)
+                .take_while(|&i| offsets[i].0 <= start_char)
+                .last()
+                .unwrap_or(context_start);
+            let end = (context_start..=context_end)
+                .rev()
+                .take_while(|&i| offsets[i].1 >= end_char)
+                .last()
+                .unwrap_or(context_end);
+
+            AnswerPositions {
+                start,
+                end,
+                impossible: false,
+            }
+        })
+        .collect()
+}
+
+#[derive(NifTaggedEnum)]
+pub enum AnswerSpansOption {
+    ContextSequenceId(usize),
+    MaxAnswerLength(usize),
+    NBest(usize),
+    TopK(usize),
+    HandleImpossibleAnswer(bool),
+    ImpossibleIndex(usize),
+}
+
+#[derive(rustler::NifMap)]
+pub struct AnswerSpan {
+    score: f32,
+    start: usize,
+    end: usize,
+    text: String,
+    window: usize,
+}
+
+fn top_indices(logits: &[f32], range: (usize, usize), n: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (range.0..=range.1).collect();
+    indices.sort_by(|&a, &b| logits[b].total_cmp(&logits[a]));
+    indices.truncate(n);
+    indices
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn encoding_answer_spans<'a>(
+    encodings: Vec<ExTokenizersEncoding>,
+    context: String,
+    start_logits: Binary<'a>,
+    end_logits: Binary<'a>,
+    options: Vec<AnswerSpansOption>,
+) -> Result<Vec<AnswerSpan>, ExTokenizersError> {
+    struct Opts {
+        context_sequence_id: usize,
+        max_answer_length: usize,
+        n_best: usize,
+        top_k: usize,
+        handle_impossible_answer: bool,
+        impossible_index: usize,
+    }
+    let mut opts = Opts {
+        context_sequence_id: 1,
+        max_answer_length: 30,
+        n_best: 20,
+        top_k: 1,
+        handle_impossible_answer: false,
+        impossible_index: 0,
+    };
+    for option in options {
+        match option {
+            AnswerSpansOption::ContextSequenceId(id) => opts.context_sequence_id = id,
+            AnswerSpansOption::MaxAnswerLength(length) => opts.max_answer_length = length,
+            AnswerSpansOption::NBest(n_best) => opts.n_best = n_best,
+            AnswerSpansOption::TopK(top_k) => opts.top_k = top_k,
+            AnswerSpansOption::HandleImpossibleAnswer(handle) => {
+                opts.handle_impossible_answer = handle
+            }
+            AnswerSpansOption::ImpossibleIndex(index) => opts.impossible_index = index,
+        }
+    }
+
+    let n_rows: usize = encodings.iter().map(|e| e.resource.0.len()).sum();
+    for logits in [&start_logits, &end_logits] {
+        if logits.len() != n_rows * 4 {
+            return Err(ExTokenizersError::Other(format!(
+                "expected logits of {} f32 values, got {} bytes",
+                n_rows,
+                logits.len()
+            )));
+        }
+    }
+    let start_logits = slice_u8_to_f32(start_logits.as_slice());
+    let end_logits = slice_u8_to_f32(end_logits.as_slice());
+
+    let mut candidates: Vec<(f32, usize, (usize, usize))> = Vec::new();
+    let mut null_answer: Option<(f32, usize)> = None;
+    let mut row = 0;
+    for (window, encoding) in encodings.iter().enumerate() {
+        let encoding = &encoding.resource.0;
+        let start_logits = &start_logits[row..row + encoding.len()];
+        let end_logits = &end_logits[row..row + encoding.len()];
+        row += encoding.len();
+
+        if opts.impossible_index < encoding.len() {
+            let score = start_logits[opts.impossible_index] + end_logits[opts.impossible_index];
+            if null_answer.is_none_or(|(null_score, _)| score < null_score) {
+                null_answer = Some((score, window));
+            }
+        }
+
+        let Some(range) = context_range(encoding, opts.context_sequence_id) else {
+            continue;
+        };
+        let sequence_ids = encoding.get_sequence_ids();
+        let offsets = encoding.get_offsets();
+        let ends = top_indices(end_logits, range, opts.n_best);
+        for start in top_indices(start_logits, range, opts.n_best) {
+            for &end in ends.iter() {
+                if end < start
+                    || end - start + 1 > opts.max_answer_length
+                    || sequence_ids[start..=end]
+                        .iter()
+                        .any(|id| *id != Some(opts.context_sequence_id))
+                {
+                    continue;
+                }
+                let score = start_logits[start] + end_logits[end];
+                candidates.push((score, window, (offsets[start].0, offsets[end].1)));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    // The same span may be predicted from overlapping windows
+    let mut seen = HashSet::new();
+    candidates.retain(|(_, _, span)| seen.insert(*span));
+
+    let mut answers: Vec<AnswerSpan> = candidates
+        .into_iter()
+        .map(|(score, window, (start, end))| AnswerSpan {
+            score,
+            start,
+            end,
+            text: context
+                .as_bytes()
+                .get(start..end)
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                .unwrap_or_default(),
+            window,
+        })
+        .collect();
+
+    if let (true, Some((score, window))) = (opts.handle_impossible_answer, null_answer) {
+        let index = answers
+            .iter()
+            .position(|answer| answer.score <= score)
+            .unwrap_or(answers.len());
+        answers.insert(
+            index,
+            AnswerSpan {
+                score,
+                start: 0,
+                end: 0,
+                text: String::new(),
+                window,
+            },
+        );
+    }
+
+    answers.truncate(opts.top_k);
+    Ok(answers)
+}
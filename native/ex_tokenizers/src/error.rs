@@ -1,31 +1,133 @@
-use rustler::{Encoder, Env, Term};
+use rustler::{Atom, Encoder, Env, Term};
 use std::{io, panic::RefUnwindSafe};
 use thiserror::Error;
 
 rustler::atoms! {
     ok,
-    error
+    error,
+    // Error struct
+    error_module = "Elixir.Tokenizers.Error",
+    struct_ = "__struct__",
+    exception = "__exception__",
+    kind,
+    message,
+    context,
+    path,
+    line,
+    column,
+    // Error kinds
+    invalid_char,
+    tokenizer,
+    io,
+    json_parse,
+    invalid_pattern,
+    invalid_template,
+    unknown_token,
+    training_panic,
+    internal,
+    other,
+    unknown,
 }
 
 #[derive(Error, Debug)]
 pub enum ExTokenizersError {
-    #[error("Invalid Char")]
+    #[error("invalid char")]
     InvalidChar,
-    #[error("Tokenizer Error")]
+    #[error("{0}")]
     Tokenizer(#[from] tokenizers::Error),
-    #[error("IO Error")]
+    #[error("{0}")]
     Io(#[from] io::Error),
-    #[error("Internal Error: {0}")]
+    #[error("{message}")]
+    JsonParse {
+        message: String,
+        path: String,
+        line: usize,
+        column: usize,
+    },
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    #[error("invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("{0}")]
+    UnknownToken(String),
+    #[error("training panicked: {0}")]
+    TrainingPanic(String),
+    #[error("internal error: {0}")]
     Internal(String),
-    #[error("Other error: {0}")]
+    #[error("{0}")]
     Other(String),
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
 
+impl ExTokenizersError {
+    // An error for JSON that failed to deserialize, with the path of the
+    // offending field, such as `model.vocab` or `added_tokens[2].content`
+    pub fn json_parse(error: serde_json::Error, path: String) -> Self {
+        Self::JsonParse {
+            message: error.to_string(),
+            path,
+            line: error.line(),
+            column: error.column(),
+        }
+    }
+
+    fn kind(&self) -> Atom {
+        match self {
+            Self::InvalidChar => invalid_char(),
+            Self::Tokenizer(error) if error.is::<io::Error>() => io(),
+            Self::Tokenizer(error) if error.is::<serde_json::Error>() => json_parse(),
+            Self::Tokenizer(_) => tokenizer(),
+            Self::Io(_) => io(),
+            Self::JsonParse { .. } => json_parse(),
+            Self::InvalidPattern(_) => invalid_pattern(),
+            Self::InvalidTemplate(_) => invalid_template(),
+            Self::UnknownToken(_) => unknown_token(),
+            Self::TrainingPanic(_) => training_panic(),
+            Self::Internal(_) => internal(),
+            Self::Other(_) => other(),
+            Self::Unknown(_) => unknown(),
+        }
+    }
+
+    fn context<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let mut pairs = Vec::new();
+        match self {
+            Self::JsonParse {
+                path: json_path,
+                line: json_line,
+                column: json_column,
+                ..
+            } => {
+                pairs.push((path().encode(env), json_path.encode(env)));
+                pairs.push((line().encode(env), json_line.encode(env)));
+                pairs.push((column().encode(env), json_column.encode(env)));
+            }
+            Self::Tokenizer(error) => {
+                // Without the source text, only the position is known
+                if let Some(error) = error.downcast_ref::<serde_json::Error>() {
+                    if error.line() > 0 {
+                        pairs.push((line().encode(env), error.line().encode(env)));
+                        pairs.push((column().encode(env), error.column().encode(env)));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Term::map_from_pairs(env, &pairs).unwrap()
+    }
+}
+
 impl Encoder for ExTokenizersError {
     fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
-        format!("{self:?}").encode(env)
+        let pairs = [
+            (struct_().encode(env), error_module().encode(env)),
+            (exception().encode(env), true.encode(env)),
+            (kind().encode(env), self.kind().encode(env)),
+            (message().encode(env), self.to_string().encode(env)),
+            (context().encode(env), self.context(env)),
+        ];
+        Term::map_from_pairs(env, &pairs).unwrap()
     }
 }
 
@@ -5,11 +5,13 @@ mod encoding;
 mod error;
 mod models;
 mod normalizers;
+mod packing;
 mod post_processors;
 mod pre_tokenizers;
 mod tokenizer;
 mod trainers;
 mod util;
+mod vocabulary;
 
 use rustler::{Env, Term};
 
@@ -1,20 +1,30 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::{Arc, Mutex, RwLock};
 
 use rustler::NifTaggedEnum;
 use serde::{Deserialize, Serialize};
-use tokenizers::models::bpe::BpeBuilder;
+use tokenizers::models::bpe::{BpeBuilder, BPE};
+use tokenizers::models::unigram::Unigram;
 use tokenizers::models::wordlevel::WordLevelBuilder;
 use tokenizers::models::wordpiece::WordPieceBuilder;
 use tokenizers::{Model, ModelWrapper};
 
 use crate::error::ExTokenizersError;
 use crate::trainers::ExTokenizersTrainer;
+use crate::util::{fingerprint, from_json_str, Rng};
 use crate::{new_info, util::Info};
 
-pub struct ExTokenizersModelRef(pub RwLock<ModelWrapper>);
+pub struct ExTokenizersModelRef(pub RwLock<ModelWrapper>, Mutex<Option<ModelIndex>>);
+
+// Lookup tables derived from a model, built on first use and kept until
+// the model is modified
+enum ModelIndex {
+    Unigram(Arc<UnigramIndex>),
+    Bpe(Arc<BpeIndex>),
+}
 
 #[rustler::resource_impl]
 impl rustler::Resource for ExTokenizersModelRef {}
@@ -90,7 +100,37 @@ impl ExTokenizersModelRef {
     where
         T: Into<ModelWrapper>,
     {
-        Self(RwLock::new(data.into()))
+        Self(RwLock::new(data.into()), Mutex::new(None))
+    }
+
+    pub fn unigram_index(&self, model: &Unigram) -> Arc<UnigramIndex> {
+        let mut index = self.1.lock().unwrap();
+        match index.deref() {
+            Some(ModelIndex::Unigram(unigram)) => unigram.clone(),
+            _ => {
+                let unigram = Arc::new(UnigramIndex::new(model));
+                *index = Some(ModelIndex::Unigram(unigram.clone()));
+                unigram
+            }
+        }
+    }
+
+    pub fn bpe_index(&self, model: &BPE) -> Result<Arc<BpeIndex>, ExTokenizersError> {
+        let mut index = self.1.lock().unwrap();
+        match index.deref() {
+            Some(ModelIndex::Bpe(bpe)) => Ok(bpe.clone()),
+            _ => {
+                let bpe = Arc::new(BpeIndex::new(model)?);
+                *index = Some(ModelIndex::Bpe(bpe.clone()));
+                Ok(bpe)
+            }
+        }
+    }
+
+    // Drops the lookup tables, which must be done whenever the model is
+    // modified in place
+    pub fn clear_index(&self) {
+        *self.1.lock().unwrap() = None;
     }
 }
 
@@ -144,12 +184,84 @@ pub fn models_save(
 }
 
 ///////////////////////////////////////////////////////////////////////////////
-/// Inspection
+/// Tokenization
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(rustler::NifMap)]
+pub struct ModelToken {
+    id: u32,
+    value: String,
+    offsets: (usize, usize),
+}
+
+impl From<tokenizers::Token> for ModelToken {
+    fn from(token: tokenizers::Token) -> Self {
+        Self {
+            id: token.id,
+            value: token.value,
+            offsets: token.offsets,
+        }
+    }
+}
+
 #[rustler::nif]
+pub fn models_tokenize(
+    model: ExTokenizersModel,
+    sequence: &str,
+) -> Result<Vec<ModelToken>, ExTokenizersError> {
+    Ok(model
+        .tokenize(sequence)?
+        .into_iter()
+        .map(ModelToken::from)
+        .collect())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_tokenize_batch(
+    model: ExTokenizersModel,
+    sequences: Vec<String>,
+) -> Result<Vec<Vec<ModelToken>>, ExTokenizersError> {
+    let model = model.resource.0.read().unwrap();
+    sequences
+        .iter()
+        .map(|sequence| {
+            Ok(model
+                .tokenize(sequence)?
+                .into_iter()
+                .map(ModelToken::from)
+                .collect())
+        })
+        .collect()
+}
+
+#[rustler::nif]
+pub fn models_token_to_id(model: ExTokenizersModel, token: &str) -> Option<u32> {
+    model.token_to_id(token)
+}
+
+#[rustler::nif]
+pub fn models_id_to_token(model: ExTokenizersModel, id: u32) -> Option<String> {
+    model.id_to_token(id)
+}
+
+#[rustler::nif]
+pub fn models_get_vocab(model: ExTokenizersModel) -> HashMap<String, u32> {
+    model.get_vocab()
+}
+
+#[rustler::nif]
+pub fn models_get_vocab_size(model: ExTokenizersModel) -> usize {
+    model.get_vocab_size()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Inspection
+///////////////////////////////////////////////////////////////////////////////
+
+#[rustler::nif(schedule = "DirtyCpu")]
 pub fn models_info(model: ExTokenizersModel) -> Info {
-    match &model.resource.0.read().unwrap().deref() {
+    let resource = &model.resource;
+    match &resource.0.read().unwrap().deref() {
         ModelWrapper::BPE(model) => new_info! {
             model_type: "bpe",
             dropout: model.dropout,
@@ -169,14 +281,25 @@ pub fn models_info(model: ExTokenizersModel) -> Info {
             model_type: "wordlevel",
             unk_token: model.unk_token.clone()
         },
-        ModelWrapper::Unigram(model) => new_info! {
-            model_type: "unigram",
-            min_score: model.min_score,
-            byte_fallback: model.byte_fallback()
-        },
+        ModelWrapper::Unigram(model) => {
+            let scores: Vec<f64> = model.iter().map(|(_, score)| *score).collect();
+            new_info! {
+                model_type: "unigram",
+                vocab_size: model.get_vocab_size(),
+                unk_id: resource.unigram_index(model).unk_id,
+                min_score: scores.iter().copied().reduce(f64::min),
+                max_score: scores.iter().copied().reduce(f64::max),
+                byte_fallback: model.byte_fallback()
+            }
+        }
     }
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_fingerprint(model: ExTokenizersModel) -> Result<String, ExTokenizersError> {
+    fingerprint(&model)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// BPE
 ///////////////////////////////////////////////////////////////////////////////
@@ -229,20 +352,146 @@ pub fn models_bpe_empty() -> Result<ExTokenizersModel, ExTokenizersError> {
     ))
 }
 
+// Reads the vocab.json and merges.txt files of a BPE model, the same way
+// as `BPE::from_file`, but locating invalid entries of vocab.json and
+// counting the lines of merges.txt from the top of the file
+#[allow(clippy::type_complexity)]
+fn read_bpe_files(
+    vocab: &str,
+    merges: &str,
+) -> Result<(HashMap<String, u32>, Vec<(String, String)>), ExTokenizersError> {
+    let vocab = from_json_str(&std::fs::read_to_string(vocab)?)?;
+    let merges = std::fs::read_to_string(merges)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with("#version"))
+        .map(
+            |(index, line)| match line.split(' ').collect::<Vec<_>>()[..] {
+                [left, right] => Ok((left.to_string(), right.to_string())),
+                _ => Err(ExTokenizersError::Tokenizer(Box::new(
+                    tokenizers::models::bpe::Error::BadMerges(index + 1),
+                ))),
+            },
+        )
+        .collect::<Result<_, _>>()?;
+    Ok((vocab, merges))
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 pub fn models_bpe_from_file(
     vocab: String,
     merges: String,
     options: Vec<BPEOption>,
 ) -> Result<ExTokenizersModel, ExTokenizersError> {
+    let (vocab, merges) = read_bpe_files(&vocab, &merges)?;
     let model = populate_bpe_options_to_builder(
-        tokenizers::models::bpe::BPE::from_file(&vocab, &merges),
+        tokenizers::models::bpe::BPE::builder().vocab_and_merges(vocab, merges),
         options,
     )
     .build()?;
     Ok(ExTokenizersModel::new(model))
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_bpe_merges(
+    model: ExTokenizersModel,
+) -> Result<Vec<(String, String)>, ExTokenizersError> {
+    let resource = &model.resource;
+    match resource.0.read().unwrap().deref() {
+        ModelWrapper::BPE(bpe) => Ok(resource.bpe_index(bpe)?.ranked_merges.clone()),
+        _ => Err(ExTokenizersError::Other(String::from(
+            "merges are only available for BPE models",
+        ))),
+    }
+}
+
+#[derive(rustler::NifMap)]
+pub struct BpeMergeStep {
+    pair: (String, String),
+    rank: u32,
+    token: String,
+}
+
+#[derive(rustler::NifUnitEnum)]
+pub enum BpeFallbackKind {
+    ByteFallback,
+    Unk,
+}
+
+#[derive(rustler::NifMap)]
+pub struct BpeFallback {
+    kind: BpeFallbackKind,
+    text: String,
+    offsets: (usize, usize),
+    tokens: Vec<String>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct BpeExplanation {
+    symbols: Vec<String>,
+    merges: Vec<BpeMergeStep>,
+    tokens: Vec<ModelToken>,
+    fallbacks: Vec<BpeFallback>,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_bpe_explain(
+    model: ExTokenizersModel,
+    word: &str,
+) -> Result<BpeExplanation, ExTokenizersError> {
+    let resource = &model.resource;
+    let model = resource.0.read().unwrap();
+    let ModelWrapper::BPE(bpe) = model.deref() else {
+        return Err(ExTokenizersError::Other(String::from(
+            "explaining merges requires a BPE model",
+        )));
+    };
+    let merger = BpeMerger::new(resource, bpe)?;
+    let token = |id: &u32| merger.index.vocab_r[id].clone();
+
+    // Words found in the vocabulary are kept whole when merges are ignored
+    if let Some(id) = merger.index.vocab.get(word).filter(|_| bpe.ignore_merges) {
+        return Ok(BpeExplanation {
+            symbols: vec![word.to_string()],
+            merges: Vec::new(),
+            tokens: vec![tokenizers::Token::new(*id, word.to_string(), (0, word.len())).into()],
+            fallbacks: Vec::new(),
+        });
+    }
+
+    let (symbols, fallbacks) = merger.symbols(word)?;
+    let (merged, merges) = merger.merge(&symbols, 0.0, &mut Rng::new(0));
+    Ok(BpeExplanation {
+        symbols: symbols.iter().map(|(id, _)| token(id)).collect(),
+        merges: merges
+            .iter()
+            .map(|((left, right), rank, id)| BpeMergeStep {
+                pair: (token(left), token(right)),
+                rank: *rank,
+                token: token(id),
+            })
+            .collect(),
+        tokens: merger
+            .tokens(&merged)
+            .into_iter()
+            .map(ModelToken::from)
+            .collect(),
+        fallbacks: fallbacks
+            .into_iter()
+            .map(|(start, end, ids, byte_fallback)| BpeFallback {
+                kind: if byte_fallback {
+                    BpeFallbackKind::ByteFallback
+                } else {
+                    BpeFallbackKind::Unk
+                },
+                text: word[start..end].to_string(),
+                offsets: (start, end),
+                tokens: ids.iter().map(token).collect(),
+            })
+            .collect(),
+    })
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 /// WordPiece
 ///////////////////////////////////////////////////////////////////////////////
@@ -351,8 +600,9 @@ pub fn models_wordlevel_from_file(
     vocab: String,
     options: Vec<WordLevelOption>,
 ) -> Result<ExTokenizersModel, ExTokenizersError> {
+    let vocab: HashMap<String, u32> = from_json_str(&std::fs::read_to_string(vocab)?)?;
     let model = populate_wordlevel_options_to_builder(
-        tokenizers::models::wordlevel::WordLevel::builder().files(vocab),
+        tokenizers::models::wordlevel::WordLevel::builder().vocab(vocab),
         options,
     )
     .build()?;
@@ -374,27 +624,51 @@ pub fn models_unigram_init(
     vocab: Vec<(String, f64)>,
     options: Vec<UnigramOption>,
 ) -> Result<ExTokenizersModel, ExTokenizersError> {
-    let unk_id = match options
-        .iter()
-        .find(|opt| matches!(opt, UnigramOption::UnkId(_)))
-        .unwrap()
-    {
-        UnigramOption::UnkId(unk_id) => Some(*unk_id),
-        _ => None,
-    };
+    struct Opts {
+        unk_id: Option<usize>,
+        byte_fallback: bool,
+    }
 
-    let byte_fallback = match options
-        .iter()
-        .find(|opt| matches!(opt, UnigramOption::ByteFallback(_)))
-        .unwrap()
-    {
-        UnigramOption::ByteFallback(byte_fallback) => *byte_fallback,
-        _ => false,
+    // Default values
+    let mut opts = Opts {
+        unk_id: None,
+        byte_fallback: false,
     };
 
-    Ok(ExTokenizersModel::new(
-        tokenizers::models::unigram::Unigram::from(vocab, unk_id, byte_fallback)?,
-    ))
+    options.into_iter().for_each(|option| match option {
+        UnigramOption::UnkId(unk_id) => opts.unk_id = Some(unk_id),
+        UnigramOption::ByteFallback(byte_fallback) => opts.byte_fallback = byte_fallback,
+    });
+
+    Ok(ExTokenizersModel::new(Unigram::from(
+        vocab,
+        opts.unk_id,
+        opts.byte_fallback,
+    )?))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+pub fn models_unigram_from_file(path: String) -> Result<ExTokenizersModel, ExTokenizersError> {
+    let model: Unigram = from_json_str(&std::fs::read_to_string(path)?)?;
+    Ok(ExTokenizersModel::new(model))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_unigram_vocab(
+    model: ExTokenizersModel,
+) -> Result<Vec<(String, f64)>, ExTokenizersError> {
+    match model.resource.0.read().unwrap().deref() {
+        ModelWrapper::Unigram(unigram) => Ok(unigram.iter().cloned().collect()),
+        _ => Err(ExTokenizersError::Other(String::from(
+            "scored vocabulary is only available for Unigram models",
+        ))),
+    }
+}
+
+// The unknown token id of a Unigram model, which is only exposed through
+// the model serialization, so it is read once per model into its index
+fn unigram_unk_id(model: &Unigram) -> Option<u64> {
+    serde_json::to_value(model).ok()?["unk_id"].as_u64()
 }
 
 #[rustler::nif]
@@ -403,3 +677,630 @@ pub fn models_unigram_empty() -> Result<ExTokenizersModel, ExTokenizersError> {
         tokenizers::models::unigram::Unigram::default(),
     ))
 }
+
+#[derive(rustler::NifMap)]
+pub struct UnigramScore {
+    viterbi: f64,
+    marginal: f64,
+    perplexity: f64,
+    pieces: Vec<(String, f64)>,
+}
+
+fn unigram_score(segmenter: &UnigramSegmenter, text: &str) -> UnigramScore {
+    let (viterbi, pieces, marginal) = segmenter.score(text);
+    let perplexity = if pieces.is_empty() {
+        1.0
+    } else {
+        (-viterbi / pieces.len() as f64).exp()
+    };
+    UnigramScore {
+        viterbi,
+        marginal,
+        perplexity,
+        pieces,
+    }
+}
+
+fn unigram_segmenter<'a>(
+    resource: &ExTokenizersModelRef,
+    model: &'a ModelWrapper,
+) -> Result<UnigramSegmenter<'a>, ExTokenizersError> {
+    match model {
+        ModelWrapper::Unigram(unigram) => Ok(UnigramSegmenter::new(resource, unigram)),
+        _ => Err(ExTokenizersError::Other(String::from(
+            "scoring requires a Unigram model",
+        ))),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_unigram_score(
+    model: ExTokenizersModel,
+    text: &str,
+) -> Result<UnigramScore, ExTokenizersError> {
+    let resource = &model.resource;
+    let model = resource.0.read().unwrap();
+    Ok(unigram_score(&unigram_segmenter(resource, &model)?, text))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn models_unigram_score_batch(
+    model: ExTokenizersModel,
+    texts: Vec<String>,
+) -> Result<Vec<UnigramScore>, ExTokenizersError> {
+    let resource = &model.resource;
+    let model = resource.0.read().unwrap();
+    let segmenter = unigram_segmenter(resource, &model)?;
+    Ok(texts
+        .iter()
+        .map(|text| unigram_score(&segmenter, text))
+        .collect())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Unigram segmentation
+///////////////////////////////////////////////////////////////////////////////
+
+// An edge of the segmentation lattice, going from the byte position it is
+// stored at to `end`. Edges without an id are unknown chars
+#[derive(Clone, Copy)]
+struct LatticeEdge {
+    end: usize,
+    id: Option<u32>,
+    score: f64,
+}
+
+// Same penalty as the Unigram model gives to unknown chars
+const UNK_PENALTY: f64 = 10.0;
+
+// The pieces of a Unigram model by text, with their id and score, and the
+// unknown token id
+pub struct UnigramIndex {
+    pieces: HashMap<String, (u32, f64)>,
+    max_length: usize,
+    unk_id: Option<u64>,
+}
+
+impl UnigramIndex {
+    fn new(model: &Unigram) -> Self {
+        let pieces: HashMap<String, (u32, f64)> = model
+            .iter()
+            .enumerate()
+            .map(|(id, (piece, score))| (piece.clone(), (id as u32, *score)))
+            .collect();
+        let max_length = pieces.keys().map(|piece| piece.len()).max().unwrap_or(0);
+        Self {
+            pieces,
+            max_length,
+            unk_id: unigram_unk_id(model),
+        }
+    }
+}
+
+// Segments words with a Unigram model, either as the n best segmentations
+// or by sampling segmentations as in subword regularization
+pub struct UnigramSegmenter<'a> {
+    model: &'a Unigram,
+    index: Arc<UnigramIndex>,
+}
+
+impl<'a> UnigramSegmenter<'a> {
+    pub fn new(resource: &ExTokenizersModelRef, model: &'a Unigram) -> Self {
+        Self {
+            model,
+            index: resource.unigram_index(model),
+        }
+    }
+
+    fn lattice(&self, word: &str) -> Vec<Vec<LatticeEdge>> {
+        let unk_score = self.model.min_score - UNK_PENALTY;
+        let mut lattice: Vec<Vec<LatticeEdge>> = (0..word.len()).map(|_| Vec::new()).collect();
+        for (start, char) in word.char_indices() {
+            let edges = &mut lattice[start];
+            for (offset, char) in word[start..].char_indices() {
+                let end = start + offset + char.len_utf8();
+                if end - start > self.index.max_length {
+                    break;
+                }
+                if let Some((id, score)) = self.index.pieces.get(&word[start..end]) {
+                    edges.push(LatticeEdge {
+                        end,
+                        id: Some(*id),
+                        score: *score,
+                    });
+                }
+            }
+            let single_end = start + char.len_utf8();
+            if !edges.iter().any(|edge| edge.end == single_end) {
+                edges.push(LatticeEdge {
+                    end: single_end,
+                    id: None,
+                    score: unk_score,
+                });
+            }
+        }
+        lattice
+    }
+
+    // Converts a path through the lattice into tokens. Consecutive unknown
+    // chars are tokenized together by the model, which takes care of the
+    // unknown token and byte fallback
+    fn tokens(
+        &self,
+        word: &str,
+        lattice: &[Vec<LatticeEdge>],
+        path: &[(usize, usize)],
+    ) -> tokenizers::Result<Vec<tokenizers::Token>> {
+        let mut tokens = Vec::new();
+        let mut unknown: Option<(usize, usize)> = None;
+        let flush = |unknown: &mut Option<(usize, usize)>,
+                     tokens: &mut Vec<tokenizers::Token>|
+         -> tokenizers::Result<()> {
+            if let Some((start, end)) = unknown.take() {
+                tokens.extend(self.model.tokenize(&word[start..end])?.into_iter().map(
+                    |mut token| {
+                        token.offsets = (token.offsets.0 + start, token.offsets.1 + start);
+                        token
+                    },
+                ));
+            }
+            Ok(())
+        };
+        for (start, index) in path {
+            let edge = &lattice[*start][*index];
+            match edge.id {
+                Some(id) => {
+                    flush(&mut unknown, &mut tokens)?;
+                    tokens.push(tokenizers::Token::new(
+                        id,
+                        word[*start..edge.end].to_string(),
+                        (*start, edge.end),
+                    ));
+                }
+                None => {
+                    let unknown_start = unknown.map_or(*start, |(start, _)| start);
+                    unknown = Some((unknown_start, edge.end));
+                }
+            }
+        }
+        flush(&mut unknown, &mut tokens)?;
+        Ok(tokens)
+    }
+
+    // The `n` best paths through the lattice with their score, best first
+    fn best_paths(
+        lattice: &[Vec<LatticeEdge>],
+        length: usize,
+        n: usize,
+    ) -> Vec<(Vec<(usize, usize)>, f64)> {
+        // Every position keeps its best partial paths as (score, start,
+        // edge index, rank of the partial path at start)
+        let mut best: Vec<Vec<(f64, usize, usize, usize)>> =
+            (0..=length).map(|_| Vec::new()).collect();
+        best[0].push((0.0, 0, 0, 0));
+        let by_score =
+            |a: &(f64, usize, usize, usize), b: &(f64, usize, usize, usize)| b.0.total_cmp(&a.0);
+        for start in 0..length {
+            if best[start].is_empty() {
+                continue;
+            }
+            best[start].sort_by(by_score);
+            best[start].truncate(n);
+            for (index, edge) in lattice[start].iter().enumerate() {
+                for rank in 0..best[start].len() {
+                    let score = best[start][rank].0 + edge.score;
+                    best[edge.end].push((score, start, index, rank));
+                }
+            }
+        }
+        best[length].sort_by(by_score);
+        best[length].truncate(n);
+
+        best[length]
+            .iter()
+            .map(|(score, start, index, rank)| {
+                let mut path = vec![(*start, *index)];
+                let (mut position, mut rank) = (*start, *rank);
+                while position > 0 {
+                    let (_, start, index, previous_rank) = best[position][rank];
+                    path.push((start, index));
+                    position = start;
+                    rank = previous_rank;
+                }
+                path.reverse();
+                (path, *score)
+            })
+            .collect()
+    }
+
+    // Forward pass, summing the weights of all the paths ending at every
+    // position, with the edges coming into every position
+    fn forward(
+        lattice: &[Vec<LatticeEdge>],
+        length: usize,
+        alpha: f64,
+    ) -> (Vec<f64>, Vec<Vec<(usize, usize)>>) {
+        let mut forward = vec![f64::NEG_INFINITY; length + 1];
+        let mut incoming: Vec<Vec<(usize, usize)>> = (0..=length).map(|_| Vec::new()).collect();
+        forward[0] = 0.0;
+        for start in 0..length {
+            if forward[start] == f64::NEG_INFINITY {
+                continue;
+            }
+            for (index, edge) in lattice[start].iter().enumerate() {
+                forward[edge.end] =
+                    log_sum_exp(forward[edge.end], forward[start] + alpha * edge.score);
+                incoming[edge.end].push((start, index));
+            }
+        }
+        (forward, incoming)
+    }
+
+    // The `n` best segmentations of the word with their log-probability,
+    // best first
+    pub fn nbest(
+        &self,
+        word: &str,
+        n: usize,
+    ) -> tokenizers::Result<Vec<(Vec<tokenizers::Token>, f64)>> {
+        if word.is_empty() {
+            return Ok(vec![(Vec::new(), 0.0)]);
+        }
+        let lattice = self.lattice(word);
+        Self::best_paths(&lattice, word.len(), n)
+            .into_iter()
+            .map(|(path, score)| Ok((self.tokens(word, &lattice, &path)?, score)))
+            .collect()
+    }
+
+    // Samples a segmentation of the word, with every segmentation weighted
+    // by its probability raised to `alpha`
+    pub fn sample(
+        &self,
+        word: &str,
+        alpha: f64,
+        rng: &mut Rng,
+    ) -> tokenizers::Result<Vec<tokenizers::Token>> {
+        if word.is_empty() {
+            return Ok(Vec::new());
+        }
+        let lattice = self.lattice(word);
+        let (forward, incoming) = Self::forward(&lattice, word.len(), alpha);
+
+        // Backward pass, sampling one edge at a time
+        let mut path = Vec::new();
+        let mut position = word.len();
+        while position > 0 {
+            let weights: Vec<f64> = incoming[position]
+                .iter()
+                .map(|(start, index)| {
+                    (forward[*start] + alpha * lattice[*start][*index].score - forward[position])
+                        .exp()
+                })
+                .collect();
+            let mut target = rng.next_f64() * weights.iter().sum::<f64>();
+            let mut chosen = incoming[position].len() - 1;
+            for (index, weight) in weights.iter().enumerate() {
+                if target < *weight {
+                    chosen = index;
+                    break;
+                }
+                target -= weight;
+            }
+            let (start, index) = incoming[position][chosen];
+            path.push((start, index));
+            position = start;
+        }
+        path.reverse();
+        self.tokens(word, &lattice, &path)
+    }
+
+    // The log-probability of the best segmentation with the score of each
+    // of its pieces, and the log-probability of the text summed over all
+    // its segmentations
+    pub fn score(&self, text: &str) -> (f64, Vec<(String, f64)>, f64) {
+        if text.is_empty() {
+            return (0.0, Vec::new(), 0.0);
+        }
+        let lattice = self.lattice(text);
+        let (path, viterbi) = Self::best_paths(&lattice, text.len(), 1).remove(0);
+        let pieces = path
+            .into_iter()
+            .map(|(start, index)| {
+                let edge = &lattice[start][index];
+                (text[start..edge.end].to_string(), edge.score)
+            })
+            .collect();
+        let (forward, _) = Self::forward(&lattice, text.len(), 1.0);
+        (viterbi, pieces, forward[text.len()])
+    }
+}
+
+fn log_sum_exp(x: f64, y: f64) -> f64 {
+    let (min, max) = if x < y { (x, y) } else { (y, x) };
+    if min == f64::NEG_INFINITY {
+        max
+    } else {
+        max + (min - max).exp().ln_1p()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// BPE merging
+///////////////////////////////////////////////////////////////////////////////
+
+// A symbol of a word being merged, linked to its neighbours. Merged
+// symbols are left with a zero length
+#[derive(Clone, Copy)]
+struct MergeSymbol {
+    id: u32,
+    prev: Option<usize>,
+    next: Option<usize>,
+    length: usize,
+}
+
+// The merges of a BPE model by rank. They are only exposed through the
+// model serialization
+fn bpe_merges(model: &BPE) -> Result<Vec<(String, String)>, ExTokenizersError> {
+    let mut value = serde_json::to_value(model)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    serde_json::from_value(value["merges"].take())
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))
+}
+
+// The vocabulary of a BPE model both ways, with the merges by rank and by
+// the pair of ids they apply to, as their rank and resulting id
+pub struct BpeIndex {
+    vocab: HashMap<String, u32>,
+    vocab_r: HashMap<u32, String>,
+    ranked_merges: Vec<(String, String)>,
+    merges: HashMap<(u32, u32), (u32, u32)>,
+}
+
+impl BpeIndex {
+    fn new(model: &BPE) -> Result<Self, ExTokenizersError> {
+        let vocab = model.get_vocab();
+        let vocab_r = vocab
+            .iter()
+            .map(|(token, id)| (*id, token.clone()))
+            .collect();
+        let prefix_length = model
+            .continuing_subword_prefix
+            .as_ref()
+            .map_or(0, |prefix| prefix.len());
+        let ranked_merges = bpe_merges(model)?;
+        let merges = ranked_merges
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, (left, right))| {
+                let merged = format!("{}{}", left, &right[prefix_length..]);
+                Some((
+                    (*vocab.get(left)?, *vocab.get(right)?),
+                    (rank as u32, *vocab.get(&merged)?),
+                ))
+            })
+            .collect();
+        Ok(Self {
+            vocab,
+            vocab_r,
+            ranked_merges,
+            merges,
+        })
+    }
+}
+
+// Tokenizes words the same way as a BPE model, but with the randomness of
+// dropout taken from a seeded generator
+pub struct BpeMerger<'a> {
+    model: &'a BPE,
+    index: Arc<BpeIndex>,
+}
+
+impl<'a> BpeMerger<'a> {
+    pub fn new(resource: &ExTokenizersModelRef, model: &'a BPE) -> Result<Self, ExTokenizersError> {
+        Ok(Self {
+            model,
+            index: resource.bpe_index(model)?,
+        })
+    }
+
+    // Splits the word into the initial symbols, as ids with their byte
+    // length, handling unknown chars like the model does. Also returns the
+    // chars that were not in the vocabulary, as their byte range with the
+    // ids they became and whether byte fallback was used
+    #[allow(clippy::type_complexity)]
+    fn symbols(
+        &self,
+        word: &str,
+    ) -> tokenizers::Result<(Vec<(u32, usize)>, Vec<(usize, usize, Vec<u32>, bool)>)> {
+        let model = self.model;
+        let mut symbols = Vec::new();
+        let mut fallbacks = Vec::new();
+        let mut unknown: Option<(u32, usize, usize)> = None;
+        let flush = |unknown: Option<(u32, usize, usize)>,
+                     symbols: &mut Vec<(u32, usize)>,
+                     fallbacks: &mut Vec<(usize, usize, Vec<u32>, bool)>| {
+            if let Some((id, start, length)) = unknown {
+                symbols.push((id, length));
+                fallbacks.push((start, start + length, vec![id], false));
+            }
+        };
+        let chars: Vec<(usize, char)> = word.char_indices().collect();
+        for (index, (start, char)) in chars.iter().enumerate() {
+            let length = char.len_utf8();
+            let mut piece = char.to_string();
+            if index > 0 {
+                if let Some(prefix) = &model.continuing_subword_prefix {
+                    piece = format!("{prefix}{piece}");
+                }
+            }
+            if index == chars.len() - 1 {
+                if let Some(suffix) = &model.end_of_word_suffix {
+                    piece = format!("{piece}{suffix}");
+                }
+            }
+
+            if let Some(id) = self.index.vocab.get(&piece) {
+                flush(unknown.take(), &mut symbols, &mut fallbacks);
+                symbols.push((*id, length));
+                continue;
+            }
+            if model.byte_fallback {
+                let bytes: Option<Vec<u32>> = piece
+                    .bytes()
+                    .map(|byte| self.index.vocab.get(&format!("<{byte:#04X}>")).copied())
+                    .collect();
+                if let Some(bytes) = bytes {
+                    symbols.extend(bytes.iter().map(|id| (*id, 1)));
+                    fallbacks.push((*start, start + length, bytes, true));
+                    continue;
+                }
+            }
+            if let Some(unk_token) = &model.unk_token {
+                let unk_id = *self.index.vocab.get(unk_token).ok_or_else(|| {
+                    format!("unk token `{unk_token}` not found in the vocabulary")
+                })?;
+                unknown = match unknown {
+                    Some((id, unknown_start, unknown_length)) if model.fuse_unk => {
+                        Some((id, unknown_start, unknown_length + length))
+                    }
+                    previous => {
+                        flush(previous, &mut symbols, &mut fallbacks);
+                        Some((unk_id, *start, length))
+                    }
+                };
+            }
+        }
+        flush(unknown, &mut symbols, &mut fallbacks);
+        Ok((symbols, fallbacks))
+    }
+
+    // Applies the merges by rank, skipping each one with the dropout
+    // probability, in the same order as the model. Also returns the
+    // applied merges, as the merged pair with its rank and resulting id
+    #[allow(clippy::type_complexity)]
+    fn merge(
+        &self,
+        symbols: &[(u32, usize)],
+        dropout: f64,
+        rng: &mut Rng,
+    ) -> (Vec<(u32, usize)>, Vec<((u32, u32), u32, u32)>) {
+        let mut symbols: Vec<MergeSymbol> = symbols
+            .iter()
+            .enumerate()
+            .map(|(index, (id, length))| MergeSymbol {
+                id: *id,
+                prev: index.checked_sub(1),
+                next: Some(index + 1).filter(|next| *next < symbols.len()),
+                length: *length,
+            })
+            .collect();
+        let mut queue: BinaryHeap<Reverse<(u32, usize, u32)>> = symbols
+            .windows(2)
+            .enumerate()
+            .filter_map(|(position, pair)| {
+                let (rank, id) = self.index.merges.get(&(pair[0].id, pair[1].id))?;
+                Some(Reverse((*rank, position, *id)))
+            })
+            .collect();
+        let mut skipped = Vec::new();
+        let mut applied = Vec::new();
+
+        while let Some(Reverse((rank, position, id))) = queue.pop() {
+            if dropout > 0.0 && rng.next_f64() < dropout {
+                skipped.push(Reverse((rank, position, id)));
+                continue;
+            }
+            queue.extend(skipped.drain(..));
+
+            let symbol = symbols[position];
+            let Some(next) = symbol.next.filter(|_| symbol.length > 0) else {
+                continue;
+            };
+            let right = symbols[next];
+            // Skip entries left over from symbols that were merged since
+            if self
+                .index
+                .merges
+                .get(&(symbol.id, right.id))
+                .is_none_or(|(_, merged)| *merged != id)
+            {
+                continue;
+            }
+
+            applied.push(((symbol.id, right.id), rank, id));
+            symbols[position] = MergeSymbol {
+                id,
+                next: right.next,
+                length: symbol.length + right.length,
+                ..symbol
+            };
+            symbols[next].length = 0;
+            if let Some(after) = right.next {
+                symbols[after].prev = Some(position);
+            }
+
+            let merged = symbols[position];
+            if let Some(prev) = merged.prev {
+                if let Some((rank, id)) = self.index.merges.get(&(symbols[prev].id, merged.id)) {
+                    queue.push(Reverse((*rank, prev, *id)));
+                }
+            }
+            if let Some(after) = merged.next {
+                if let Some((rank, id)) = self.index.merges.get(&(merged.id, symbols[after].id)) {
+                    queue.push(Reverse((*rank, position, *id)));
+                }
+            }
+        }
+
+        let symbols = symbols
+            .into_iter()
+            .filter(|symbol| symbol.length > 0)
+            .map(|symbol| (symbol.id, symbol.length))
+            .collect();
+        (symbols, applied)
+    }
+
+    // Tokenizes a word, skipping every merge with the `dropout`
+    // probability
+    pub fn tokenize(
+        &self,
+        word: &str,
+        dropout: f64,
+        rng: &mut Rng,
+    ) -> tokenizers::Result<Vec<tokenizers::Token>> {
+        // Like the model, words found in the vocabulary are kept whole when
+        // merges are ignored
+        if let Some(id) = self
+            .index
+            .vocab
+            .get(word)
+            .filter(|_| self.model.ignore_merges)
+        {
+            return Ok(vec![tokenizers::Token::new(
+                *id,
+                word.to_string(),
+                (0, word.len()),
+            )]);
+        }
+        let (symbols, _) = self.symbols(word)?;
+        let (symbols, _) = self.merge(&symbols, dropout, rng);
+        Ok(self.tokens(&symbols))
+    }
+
+    fn tokens(&self, symbols: &[(u32, usize)]) -> Vec<tokenizers::Token> {
+        let mut offset = 0;
+        symbols
+            .iter()
+            .map(|(id, length)| {
+                let token = tokenizers::Token::new(
+                    *id,
+                    self.index.vocab_r[id].clone(),
+                    (offset, offset + length),
+                );
+                offset += length;
+                token
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,5 @@
+use crate::util::fingerprint;
+use crate::util::{normalized_alignments, normalized_char_alignments, OffsetUnit};
 use crate::{new_info, util::Info, ExTokenizersError};
 use rustler::NifTaggedEnum;
 use serde::{Deserialize, Serialize};
@@ -81,6 +83,47 @@ fn normalizers_normalize(
     Ok(normalized.get().to_owned())
 }
 
+#[derive(NifTaggedEnum)]
+pub enum NormalizeOption {
+    Unit(OffsetUnit),
+}
+
+#[derive(rustler::NifMap)]
+pub struct NormalizedAlignments {
+    normalized: String,
+    alignments: Vec<(usize, usize)>,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn normalizers_normalize_with_alignments(
+    normalizer: ExTokenizersNormalizer,
+    input: String,
+    options: Vec<NormalizeOption>,
+) -> Result<NormalizedAlignments, ExTokenizersError> {
+    struct Opts {
+        unit: OffsetUnit,
+    }
+    let mut opts = Opts {
+        unit: OffsetUnit::Byte,
+    };
+    for option in options {
+        match option {
+            NormalizeOption::Unit(unit) => opts.unit = unit,
+        }
+    }
+
+    let mut normalized = NormalizedString::from(input);
+    normalizer.resource.0.normalize(&mut normalized)?;
+    let alignments = match opts.unit {
+        OffsetUnit::Byte => normalized_alignments(&normalized),
+        OffsetUnit::Char => normalized_char_alignments(&normalized),
+    };
+    Ok(NormalizedAlignments {
+        normalized: normalized.get().to_owned(),
+        alignments,
+    })
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Inspection
 // /////////////////////////////////////////////////////////////////////////////
@@ -133,6 +176,13 @@ fn normalizers_info(normalizer: ExTokenizersNormalizer) -> Info {
     }
 }
 
+#[rustler::nif]
+fn normalizers_fingerprint(
+    normalizer: ExTokenizersNormalizer,
+) -> Result<String, ExTokenizersError> {
+    fingerprint(&normalizer)
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Implementation
 // /////////////////////////////////////////////////////////////////////////////
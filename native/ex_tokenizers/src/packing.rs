@@ -0,0 +1,228 @@
+use rustler::{Binary, Env, NifTaggedEnum, NifUntaggedEnum};
+
+use crate::encoding::ExTokenizersEncoding;
+use crate::error::ExTokenizersError;
+use crate::util::u32_binary;
+
+#[derive(NifUntaggedEnum)]
+pub enum PackInput {
+    Encoding(ExTokenizersEncoding),
+    Ids(Vec<u32>),
+}
+
+impl PackInput {
+    fn ids(&self) -> Vec<u32> {
+        match self {
+            PackInput::Encoding(encoding) => {
+                let encoding = &encoding.resource.0;
+                encoding
+                    .get_ids()
+                    .iter()
+                    .zip(encoding.get_attention_mask())
+                    .filter(|(_, mask)| **mask == 1)
+                    .map(|(id, _)| *id)
+                    .collect()
+            }
+            PackInput::Ids(ids) => ids.clone(),
+        }
+    }
+}
+
+#[derive(rustler::NifUnitEnum, Clone, Copy, PartialEq)]
+pub enum PackStrategy {
+    Greedy,
+    FirstFitDecreasing,
+}
+
+#[derive(NifTaggedEnum)]
+pub enum PackOption {
+    Strategy(PackStrategy),
+    SeparatorId(Option<u32>),
+    PadId(u32),
+}
+
+#[derive(rustler::NifMap)]
+pub struct PackedSpan {
+    document: usize,
+    offset: usize,
+    row: usize,
+    start: usize,
+    length: usize,
+}
+
+#[derive(rustler::NifMap)]
+pub struct Packed<'a> {
+    ids: Binary<'a>,
+    attention_mask: Binary<'a>,
+    position_ids: Binary<'a>,
+    segment_ids: Binary<'a>,
+    shape: (usize, usize),
+    spans: Vec<PackedSpan>,
+}
+
+// A piece is a whole document, or a chunk of a document that is longer
+// than a row, together with its separator.
+struct Piece {
+    document: usize,
+    offset: usize,
+    ids: Vec<u32>,
+}
+
+// Max segment tree over the remaining capacity of rows, used to find the
+// first row a piece fits in. Leaves past the last opened row have full
+// capacity, so the first fit is either an opened row or the next new one.
+struct Capacities {
+    size: usize,
+    tree: Vec<usize>,
+}
+
+impl Capacities {
+    fn new(rows: usize, capacity: usize) -> Self {
+        let size = rows.next_power_of_two();
+        let mut tree = vec![0; 2 * size];
+        tree[size..size + rows].fill(capacity);
+        for node in (1..size).rev() {
+            tree[node] = tree[2 * node].max(tree[2 * node + 1]);
+        }
+        Self { size, tree }
+    }
+
+    fn first_fit(&self, length: usize) -> usize {
+        let mut node = 1;
+        while node < self.size {
+            node = if self.tree[2 * node] >= length {
+                2 * node
+            } else {
+                2 * node + 1
+            };
+        }
+        node - self.size
+    }
+
+    fn take(&mut self, row: usize, length: usize) {
+        let mut node = row + self.size;
+        self.tree[node] -= length;
+        while node > 1 {
+            node /= 2;
+            self.tree[node] = self.tree[2 * node].max(self.tree[2 * node + 1]);
+        }
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn packing_pack<'a>(
+    env: Env<'a>,
+    documents: Vec<PackInput>,
+    length: usize,
+    options: Vec<PackOption>,
+) -> Result<Packed<'a>, ExTokenizersError> {
+    struct Opts {
+        strategy: PackStrategy,
+        separator_id: Option<u32>,
+        pad_id: u32,
+    }
+    let mut opts = Opts {
+        strategy: PackStrategy::Greedy,
+        separator_id: None,
+        pad_id: 0,
+    };
+    for option in options {
+        match option {
+            PackOption::Strategy(strategy) => opts.strategy = strategy,
+            PackOption::SeparatorId(separator_id) => opts.separator_id = separator_id,
+            PackOption::PadId(pad_id) => opts.pad_id = pad_id,
+        }
+    }
+
+    if length == 0 {
+        return Err(ExTokenizersError::Other(String::from(
+            "row length must be greater than 0",
+        )));
+    }
+
+    let mut pieces: Vec<Piece> = Vec::new();
+    for (document, input) in documents.iter().enumerate() {
+        let mut ids = input.ids();
+        ids.extend(opts.separator_id);
+        for (chunk, ids) in ids.chunks(length).enumerate() {
+            pieces.push(Piece {
+                document,
+                offset: chunk * length,
+                ids: ids.to_vec(),
+            });
+        }
+    }
+
+    let mut order: Vec<usize> = (0..pieces.len()).collect();
+    if opts.strategy == PackStrategy::FirstFitDecreasing {
+        order.sort_by_key(|&piece| std::cmp::Reverse(pieces[piece].ids.len()));
+    }
+
+    let mut rows: Vec<Vec<usize>> = Vec::new();
+    match opts.strategy {
+        PackStrategy::Greedy => {
+            let mut used = length;
+            for piece in order {
+                let piece_length = pieces[piece].ids.len();
+                if used + piece_length > length {
+                    rows.push(Vec::new());
+                    used = 0;
+                }
+                rows.last_mut().unwrap().push(piece);
+                used += piece_length;
+            }
+        }
+        PackStrategy::FirstFitDecreasing => {
+            let mut capacities = Capacities::new(pieces.len().max(1), length);
+            for piece in order {
+                let piece_length = pieces[piece].ids.len();
+                let row = capacities.first_fit(piece_length);
+                capacities.take(row, piece_length);
+                if row == rows.len() {
+                    rows.push(Vec::new());
+                }
+                rows[row].push(piece);
+            }
+        }
+    }
+
+    let size = rows.len() * length;
+    let mut ids = vec![opts.pad_id; size];
+    let mut attention_mask = vec![0; size];
+    let mut position_ids = vec![0; size];
+    let mut segment_ids = vec![0; size];
+    let mut spans = Vec::with_capacity(pieces.len());
+    for (row, row_pieces) in rows.iter().enumerate() {
+        let mut start = 0;
+        for (segment, &piece) in row_pieces.iter().enumerate() {
+            let piece = &pieces[piece];
+            let from = row * length + start;
+            let to = from + piece.ids.len();
+            ids[from..to].copy_from_slice(&piece.ids);
+            attention_mask[from..to].fill(1);
+            position_ids[from..to]
+                .iter_mut()
+                .zip(0..)
+                .for_each(|(position, index)| *position = index);
+            segment_ids[from..to].fill(segment as u32 + 1);
+            spans.push(PackedSpan {
+                document: piece.document,
+                offset: piece.offset,
+                row,
+                start,
+                length: piece.ids.len(),
+            });
+            start += piece.ids.len();
+        }
+    }
+    spans.sort_by_key(|span| (span.document, span.offset));
+
+    Ok(Packed {
+        ids: u32_binary(env, &ids),
+        attention_mask: u32_binary(env, &attention_mask),
+        position_ids: u32_binary(env, &position_ids),
+        segment_ids: u32_binary(env, &segment_ids),
+        shape: (rows.len(), length),
+        spans,
+    })
+}
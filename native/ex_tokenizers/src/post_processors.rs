@@ -2,6 +2,8 @@ use rustler::NifTaggedEnum;
 use serde::{Deserialize, Serialize};
 use tokenizers::{Encoding, PostProcessorWrapper};
 
+use crate::error::ExTokenizersError;
+use crate::util::fingerprint;
 use crate::{new_info, util::Info};
 
 pub struct ExTokenizersPostProcessorRef(pub PostProcessorWrapper);
@@ -95,6 +97,13 @@ fn post_processors_info(post_processor: ExTokenizersPostProcessor) -> Info {
     }
 }
 
+#[rustler::nif]
+fn post_processors_fingerprint(
+    post_processor: ExTokenizersPostProcessor,
+) -> Result<String, ExTokenizersError> {
+    fingerprint(&post_processor)
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Implementation
 // /////////////////////////////////////////////////////////////////////////////
@@ -151,19 +160,20 @@ pub enum TemplateOption {
 #[rustler::nif]
 pub fn post_processors_template(
     opts: Vec<TemplateOption>,
-) -> Result<ExTokenizersPostProcessor, rustler::Error> {
+) -> Result<ExTokenizersPostProcessor, ExTokenizersError> {
+    let invalid_template = |error: String| ExTokenizersError::InvalidTemplate(error);
     let mut builder = tokenizers::processors::template::TemplateProcessing::builder();
     for opt in opts {
         match opt {
-            TemplateOption::Single(v) => {
-                builder.try_single(v).map_err(|_| rustler::Error::BadArg)?
-            }
-            TemplateOption::Pair(v) => builder.try_pair(v).map_err(|_| rustler::Error::BadArg)?,
+            TemplateOption::Single(v) => builder.try_single(v).map_err(invalid_template)?,
+            TemplateOption::Pair(v) => builder.try_pair(v).map_err(invalid_template)?,
             TemplateOption::SpecialTokens(v) => builder.special_tokens(v),
         };
     }
     Ok(ExTokenizersPostProcessor::new(
-        builder.build().map_err(|_| rustler::Error::BadArg)?,
+        builder
+            .build()
+            .map_err(|error| invalid_template(error.to_string()))?,
     ))
 }
 
@@ -1,4 +1,4 @@
-use crate::util::Info;
+use crate::util::{fingerprint, Info};
 use crate::{new_info, ExTokenizersError};
 use rustler::NifTaggedEnum;
 use serde::{Deserialize, Serialize};
@@ -134,6 +134,13 @@ fn pre_tokenizers_info(pre_tokenizer: ExTokenizersPreTokenizer) -> Info {
     }
 }
 
+#[rustler::nif]
+fn pre_tokenizers_fingerprint(
+    pre_tokenizer: ExTokenizersPreTokenizer,
+) -> Result<String, ExTokenizersError> {
+    fingerprint(&pre_tokenizer)
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Implementation
 // /////////////////////////////////////////////////////////////////////////////
@@ -1,27 +1,35 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 use std::panic;
+use std::sync::{Arc, Mutex};
 
-use rustler::{NifTaggedEnum, Term};
+use rustler::{Binary, Env, NifTaggedEnum, NifUntaggedEnum, Term};
 
 use tokenizers::models::wordpiece::WordPieceTrainerBuilder;
 use tokenizers::models::TrainerWrapper;
 use tokenizers::tokenizer::AddedToken;
-use tokenizers::Model;
-use tokenizers::{EncodeInput, TokenizerImpl};
+use tokenizers::utils::parallelism::MaybeParallelIterator;
+use tokenizers::{
+    pad_encodings, Model, ModelWrapper, OffsetReferential, OffsetType, PostProcessor,
+    PreTokenizedString, PreTokenizer, Token,
+};
+use tokenizers::{EncodeInput, Encoding, InputSequence, TokenizerImpl, TruncationDirection};
 
 use crate::added_token::{AddedSpecialTokenInput, AddedTokenInput};
 use crate::decoders::ExTokenizersDecoder;
 use crate::encoding::{apply_transformations, ExTokenizersEncoding, TransformationElement};
 use crate::error::ExTokenizersError;
-use crate::models::ExTokenizersModel;
+use crate::models::{BpeMerger, ExTokenizersModel, UnigramSegmenter};
 use crate::normalizers::ExTokenizersNormalizer;
 use crate::post_processors::ExTokenizersPostProcessor;
 use crate::pre_tokenizers::ExTokenizersPreTokenizer;
 use crate::trainers::ExTokenizersTrainer;
-use crate::util::Direction;
+use crate::util::{
+    from_json_str, i32_binary, json_fingerprint, normalized_alignments, u32_binary, Direction, Rng,
+};
+use crate::vocabulary::special_token_ids;
 
-type ExTokenizerImpl = TokenizerImpl<
+pub type ExTokenizerImpl = TokenizerImpl<
     ExTokenizersModel,
     ExTokenizersNormalizer,
     ExTokenizersPreTokenizer,
@@ -29,11 +37,66 @@ type ExTokenizerImpl = TokenizerImpl<
     ExTokenizersDecoder,
 >;
 
-pub struct ExTokenizersTokenizerRef(pub ExTokenizerImpl);
+pub struct ExTokenizersTokenizerRef(pub ExTokenizerImpl, Mutex<Option<Arc<TokenizerVariants>>>);
 
 #[rustler::resource_impl]
 impl rustler::Resource for ExTokenizersTokenizerRef {}
 
+// Copies of a tokenizer with some of its settings changed, built on first
+// use. The tokenizer of a resource never changes, so they are kept for as
+// long as the resource
+struct TokenizerVariants {
+    // Without padding, for batches padded once all the inputs are encoded
+    unpadded: ExTokenizerImpl,
+    // With special tokens handled the other way round
+    toggled: ExTokenizerImpl,
+    // Without truncation and padding, with special tokens matched (at
+    // index 0) or encoded as ordinary text (at index 1), used to encode
+    // sequences on their own before post-processing
+    sequence: [ExTokenizerImpl; 2],
+    // Like `sequence`, but without a prefix space at the start of the
+    // input, for text that follows other text
+    continuation: [ExTokenizerImpl; 2],
+}
+
+impl TokenizerVariants {
+    fn new(tokenizer: &ExTokenizerImpl) -> Result<Self, ExTokenizersError> {
+        let mut unpadded = tokenizer.clone();
+        unpadded.with_padding(None);
+        let mut toggled = tokenizer.clone();
+        toggled.set_encode_special_tokens(!tokenizer.get_encode_special_tokens());
+        let mut plain = unpadded.clone();
+        plain.with_truncation(None)?;
+        plain.set_encode_special_tokens(false);
+        let mut sanitized = plain.clone();
+        sanitized.set_encode_special_tokens(true);
+        let continuation = [
+            without_prefix_space(&plain)?,
+            without_prefix_space(&sanitized)?,
+        ];
+        Ok(Self {
+            unpadded,
+            toggled,
+            sequence: [plain, sanitized],
+            continuation,
+        })
+    }
+}
+
+impl ExTokenizersTokenizerRef {
+    fn variants(&self) -> Result<Arc<TokenizerVariants>, ExTokenizersError> {
+        let mut variants = self.1.lock().unwrap();
+        match variants.deref() {
+            Some(variants) => Ok(variants.clone()),
+            None => {
+                let new_variants = Arc::new(TokenizerVariants::new(&self.0)?);
+                *variants = Some(new_variants.clone());
+                Ok(new_variants)
+            }
+        }
+    }
+}
+
 #[derive(rustler::NifStruct)]
 #[module = "Tokenizers.Tokenizer"]
 pub struct ExTokenizersTokenizer {
@@ -43,7 +106,7 @@ pub struct ExTokenizersTokenizer {
 impl From<ExTokenizerImpl> for ExTokenizersTokenizer {
     fn from(data: ExTokenizerImpl) -> Self {
         Self {
-            resource: rustler::ResourceArc::new(ExTokenizersTokenizerRef(data)),
+            resource: rustler::ResourceArc::new(ExTokenizersTokenizerRef(data, Mutex::new(None))),
         }
     }
 }
@@ -73,7 +136,8 @@ pub fn tokenizer_from_file(
     path: &str,
     options: Vec<LoadOption>,
 ) -> Result<ExTokenizersTokenizer, ExTokenizersError> {
-    let mut tokenizer = TokenizerImpl::from_file(path)?;
+    let data = std::fs::read_to_string(path)?;
+    let mut tokenizer: ExTokenizerImpl = from_json_str(&data)?;
     tokenizer = apply_load_options(tokenizer, options);
     Ok(tokenizer.into())
 }
@@ -83,7 +147,7 @@ pub fn tokenizer_from_buffer(
     data: String,
     options: Vec<LoadOption>,
 ) -> Result<ExTokenizersTokenizer, ExTokenizersError> {
-    let mut tokenizer: ExTokenizerImpl = data.parse()?;
+    let mut tokenizer: ExTokenizerImpl = from_json_str(&data)?;
     tokenizer = apply_load_options(tokenizer, options);
     Ok(tokenizer.into())
 }
@@ -412,6 +476,21 @@ pub fn tokenizer_disable_padding(tokenizer: ExTokenizersTokenizer) -> ExTokenize
     new_tokenizer.into()
 }
 
+#[rustler::nif]
+pub fn tokenizer_set_encode_special_tokens(
+    tokenizer: ExTokenizersTokenizer,
+    value: bool,
+) -> ExTokenizersTokenizer {
+    let mut new_tokenizer = tokenizer.resource.0.clone();
+    new_tokenizer.set_encode_special_tokens(value);
+    new_tokenizer.into()
+}
+
+#[rustler::nif]
+pub fn tokenizer_get_encode_special_tokens(tokenizer: ExTokenizersTokenizer) -> bool {
+    tokenizer.resource.0.get_encode_special_tokens()
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Inference
 // /////////////////////////////////////////////////////////////////////////////
@@ -432,21 +511,584 @@ fn term_to_encode_input<'a, 'b>(term: &'a Term<'b>) -> Result<EncodeInput<'b>, E
 pub enum EncodeOption {
     AddSpecialTokens(bool),
     EncodingTransformations(Vec<TransformationElement>),
+    Overflowing(OverflowingMode),
+    EncodeSpecialTokens(EncodeSpecialTokens),
+    ProtectedSpans(Vec<ProtectedSpan>),
+    Sampling(Vec<SamplingOption>),
+    Dropout(Vec<DropoutOption>),
+}
+
+#[derive(NifTaggedEnum)]
+pub enum SamplingOption {
+    Alpha(f64),
+    Seed(u64),
+}
+
+#[derive(NifTaggedEnum)]
+pub enum DropoutOption {
+    Probability(f64),
+    Seed(u64),
+}
+
+// Word segmentations drawn at random on every call, from a seeded
+// generator
+#[derive(Clone, Copy)]
+enum RandomSegmentation {
+    // Unigram sampling with the given alpha
+    Sampling(f64),
+    // BPE merges skipped with the given probability
+    Dropout(f64),
+}
+
+fn sampling_params(options: Vec<SamplingOption>) -> (RandomSegmentation, u64) {
+    let mut alpha = 0.1;
+    let mut seed = None;
+    for option in options {
+        match option {
+            SamplingOption::Alpha(value) => alpha = value,
+            SamplingOption::Seed(value) => seed = Some(value),
+        }
+    }
+    (
+        RandomSegmentation::Sampling(alpha),
+        seed.unwrap_or_else(Rng::random_seed),
+    )
+}
+
+fn dropout_params(options: Vec<DropoutOption>) -> (RandomSegmentation, u64) {
+    let mut probability = 0.1;
+    let mut seed = None;
+    for option in options {
+        match option {
+            DropoutOption::Probability(value) => probability = value,
+            DropoutOption::Seed(value) => seed = Some(value),
+        }
+    }
+    (
+        RandomSegmentation::Dropout(probability),
+        seed.unwrap_or_else(Rng::random_seed),
+    )
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum ProtectedSpan {
+    Literal(String),
+    LiteralId((String, u32)),
+    Span((usize, usize)),
+    SpanId((usize, usize, u32)),
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum EncodeSpecialTokens {
+    All(bool),
+    PerSequence(Vec<bool>),
+}
+
+impl EncodeSpecialTokens {
+    fn for_sequence(&self, index: usize) -> bool {
+        match self {
+            EncodeSpecialTokens::All(value) => *value,
+            EncodeSpecialTokens::PerSequence(values) => values.get(index).copied().unwrap_or(false),
+        }
+    }
+}
+
+// Resolves the special tokens handling of each sequence of an input,
+// defaulting to the tokenizer setting
+fn per_sequence(
+    encode_special_tokens: &Option<EncodeSpecialTokens>,
+    tokenizer: &ExTokenizerImpl,
+) -> Result<(bool, bool), ExTokenizersError> {
+    match encode_special_tokens {
+        None => {
+            let value = tokenizer.get_encode_special_tokens();
+            Ok((value, value))
+        }
+        Some(EncodeSpecialTokens::All(value)) => Ok((*value, *value)),
+        Some(EncodeSpecialTokens::PerSequence(values)) => match values[..] {
+            [value] => Ok((value, value)),
+            [first, second] => Ok((first, second)),
+            _ => Err(ExTokenizersError::Other(String::from(
+                "expected one or two values for :encode_special_tokens",
+            ))),
+        },
+    }
+}
+
+// Runs `fun` with the tokenizer, or a copy of it, set to the given special
+// tokens handling
+fn with_encode_special_tokens<T>(
+    resource: &ExTokenizersTokenizerRef,
+    encode_special_tokens: bool,
+    fun: impl FnOnce(&ExTokenizerImpl) -> Result<T, ExTokenizersError>,
+) -> Result<T, ExTokenizersError> {
+    match resource.0.get_encode_special_tokens() == encode_special_tokens {
+        true => fun(&resource.0),
+        false => fun(&resource.variants()?.toggled),
+    }
+}
+
+// Encodes the input like `TokenizerImpl::encode`, but with the special
+// tokens handling set separately for each sequence and with the given
+// protected spans
+fn encode_per_sequence(
+    tokenizer: &ExTokenizerImpl,
+    variants: &TokenizerVariants,
+    input: EncodeInput,
+    encode_special_tokens: (bool, bool),
+    protected_spans: &[ProtectedSpan],
+    add_special_tokens: bool,
+) -> Result<Encoding, ExTokenizersError> {
+    let (sequence, pair) = match input {
+        EncodeInput::Single(sequence) => (sequence, None),
+        EncodeInput::Dual(sequence, pair) => (sequence, Some(pair)),
+    };
+    if pair.is_some()
+        && protected_spans
+            .iter()
+            .any(|span| matches!(span, ProtectedSpan::Span(_) | ProtectedSpan::SpanId(_)))
+    {
+        return Err(ExTokenizersError::Other(String::from(
+            "only literal protected spans are supported when encoding a pair",
+        )));
+    }
+    let sequence = encode_protected(variants, encode_special_tokens.0, sequence, protected_spans)?;
+    let pair = match pair {
+        Some(pair) => Some(encode_protected(
+            variants,
+            encode_special_tokens.1,
+            pair,
+            protected_spans,
+        )?),
+        None => None,
+    };
+    Ok(tokenizer.post_process(sequence, pair, add_special_tokens)?)
+}
+
+// Encodes a sequence split at the protected spans, so that no token
+// crosses their boundaries. Each piece is encoded on its own, and spans
+// with an id become a single token
+fn encode_protected(
+    variants: &TokenizerVariants,
+    encode_special_tokens: bool,
+    sequence: InputSequence,
+    protected_spans: &[ProtectedSpan],
+) -> Result<Encoding, ExTokenizersError> {
+    let tokenizer = &variants.sequence[encode_special_tokens as usize];
+    if protected_spans.is_empty() {
+        return Ok(tokenizer.encode(sequence, false)?);
+    }
+    let InputSequence::Raw(text) = sequence else {
+        return Err(ExTokenizersError::Other(String::from(
+            "protected spans are only supported for raw text",
+        )));
+    };
+
+    let mut spans: Vec<(usize, usize, Option<u32>)> = Vec::new();
+    for span in protected_spans {
+        match span {
+            ProtectedSpan::Span((start, end)) => spans.push((*start, *end, None)),
+            ProtectedSpan::SpanId((start, end, id)) => spans.push((*start, *end, Some(*id))),
+            ProtectedSpan::Literal(literal) if !literal.is_empty() => spans.extend(
+                text.match_indices(literal.as_str())
+                    .map(|(start, _)| (start, start + literal.len(), None)),
+            ),
+            ProtectedSpan::LiteralId((literal, id)) if !literal.is_empty() => spans.extend(
+                text.match_indices(literal.as_str())
+                    .map(|(start, _)| (start, start + literal.len(), Some(*id))),
+            ),
+            _ => {}
+        }
+    }
+    spans.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut pieces: Vec<(usize, usize, Option<u32>)> = Vec::new();
+    let mut position = 0;
+    for (start, end, id) in spans {
+        if start > end
+            || end > text.len()
+            || !text.is_char_boundary(start)
+            || !text.is_char_boundary(end)
+        {
+            return Err(ExTokenizersError::Other(format!(
+                "invalid protected span {start}..{end}"
+            )));
+        }
+        if start < position {
+            return Err(ExTokenizersError::Other(format!(
+                "protected span {start}..{end} overlaps another span"
+            )));
+        }
+        if start > position {
+            pieces.push((position, start, None));
+        }
+        pieces.push((start, end, id));
+        position = end;
+    }
+    if position < text.len() {
+        pieces.push((position, text.len(), None));
+    }
+
+    // Only the piece at the start of the sequence gets a prefix space, as
+    // with an encoding of the whole sequence
+    let mut encoding = Encoding::default();
+    let mut word = 0;
+    for (start, end, id) in pieces {
+        let piece = match (id, start) {
+            (Some(id), _) => match tokenizer.id_to_token(id) {
+                Some(token) => Encoding::new(
+                    vec![id],
+                    vec![0],
+                    vec![token],
+                    vec![Some(0)],
+                    vec![(0, end - start)],
+                    vec![0],
+                    vec![1],
+                    Vec::new(),
+                    HashMap::new(),
+                ),
+                None => {
+                    return Err(ExTokenizersError::UnknownToken(format!(
+                        "id {id} of protected span {start}..{end} is not in the vocabulary"
+                    )))
+                }
+            },
+            (None, 0) => tokenizer.encode(&text[start..end], false)?,
+            (None, _) => variants.continuation[encode_special_tokens as usize]
+                .encode(&text[start..end], false)?,
+        };
+        merge_shifted(&mut encoding, piece, start, &mut word);
+    }
+    Ok(encoding)
+}
+
+// Appends the encoding of text found at `offset` of a sequence, shifting
+// its offsets accordingly and numbering its words after the `words`
+// already in the encoding
+fn merge_shifted(encoding: &mut Encoding, mut piece: Encoding, offset: usize, words: &mut u32) {
+    piece.get_offsets_mut().iter_mut().for_each(|(start, end)| {
+        *start += offset;
+        *end += offset;
+    });
+    let mut next_word = *words;
+    piece
+        .get_word_ids_mut()
+        .iter_mut()
+        .flatten()
+        .for_each(|id| {
+            *id += *words;
+            next_word = next_word.max(*id + 1);
+        });
+    *words = next_word;
+    encoding.merge_with(piece, false);
+}
+
+// A copy of the tokenizer whose normalizer and pre-tokenizer do not add a
+// prefix space at the start of the input, for encoding text that follows
+// other text
+fn without_prefix_space(tokenizer: &ExTokenizerImpl) -> Result<ExTokenizerImpl, ExTokenizersError> {
+    fn disable_prefix(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                match object.get("type").and_then(|value| value.as_str()) {
+                    Some("Metaspace") => {
+                        object.insert("prepend_scheme".into(), "never".into());
+                    }
+                    Some("ByteLevel") => {
+                        object.insert("add_prefix_space".into(), false.into());
+                    }
+                    Some("Prepend") => {
+                        object.insert("prepend".into(), "".into());
+                    }
+                    _ => {}
+                }
+                object.values_mut().for_each(disable_prefix);
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(disable_prefix),
+            _ => {}
+        }
+    }
+
+    fn rebuild<T: serde::Serialize + serde::de::DeserializeOwned>(
+        component: &T,
+    ) -> Result<T, ExTokenizersError> {
+        let mut value = serde_json::to_value(component)
+            .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+        disable_prefix(&mut value);
+        serde_json::from_value(value)
+            .map_err(|error| ExTokenizersError::Internal(error.to_string()))
+    }
+
+    let mut inner_tokenizer = tokenizer.clone();
+    if let Some(normalizer) = tokenizer.get_normalizer() {
+        inner_tokenizer.with_normalizer(Some(rebuild(normalizer)?));
+    }
+    if let Some(pre_tokenizer) = tokenizer.get_pre_tokenizer() {
+        inner_tokenizer.with_pre_tokenizer(Some(rebuild(pre_tokenizer)?));
+    }
+    Ok(inner_tokenizer)
+}
+
+// Runs `fun` with a segmenter for the tokenizer model, which must be a
+// Unigram model
+fn with_unigram<T>(
+    tokenizer: &ExTokenizerImpl,
+    fun: impl FnOnce(&UnigramSegmenter) -> Result<T, ExTokenizersError>,
+) -> Result<T, ExTokenizersError> {
+    let resource = &tokenizer.get_model().resource;
+    let model = resource.0.read().unwrap();
+    match model.deref() {
+        ModelWrapper::Unigram(unigram) => fun(&UnigramSegmenter::new(resource, unigram)),
+        _ => Err(ExTokenizersError::Other(String::from(
+            "n-best segmentation requires a Unigram model",
+        ))),
+    }
+}
+
+type SegmentFn<'a> = dyn Fn(&str, &mut Rng) -> tokenizers::Result<Vec<Token>> + 'a;
+
+// Runs `fun` with a function segmenting words at random, which requires a
+// Unigram model for sampling and a BPE model for dropout
+fn with_random_segmentation<T>(
+    tokenizer: &ExTokenizerImpl,
+    segmentation: RandomSegmentation,
+    fun: impl FnOnce(&SegmentFn) -> Result<T, ExTokenizersError>,
+) -> Result<T, ExTokenizersError> {
+    let resource = &tokenizer.get_model().resource;
+    let model = resource.0.read().unwrap();
+    match (segmentation, model.deref()) {
+        (RandomSegmentation::Sampling(alpha), ModelWrapper::Unigram(unigram)) => {
+            let segmenter = UnigramSegmenter::new(resource, unigram);
+            fun(&|word, rng| segmenter.sample(word, alpha, rng))
+        }
+        (RandomSegmentation::Dropout(probability), ModelWrapper::BPE(bpe)) => {
+            let merger = BpeMerger::new(resource, bpe)?;
+            fun(&|word, rng| merger.tokenize(word, probability, rng))
+        }
+        (RandomSegmentation::Sampling(_), _) => Err(ExTokenizersError::Other(String::from(
+            "segmentation sampling requires a Unigram model",
+        ))),
+        (RandomSegmentation::Dropout(_), _) => Err(ExTokenizersError::Other(String::from(
+            "dropout requires a BPE model",
+        ))),
+    }
+}
+
+fn raw_sequences<'a>(input: &'a EncodeInput) -> Result<Vec<&'a str>, ExTokenizersError> {
+    let sequences = match input {
+        EncodeInput::Single(sequence) => vec![sequence],
+        EncodeInput::Dual(sequence, pair) => vec![sequence, pair],
+    };
+    sequences
+        .into_iter()
+        .map(|sequence| match sequence {
+            InputSequence::Raw(text) => Ok(text.as_ref()),
+            _ => Err(ExTokenizersError::Other(String::from(
+                "random segmentation and n-best are only supported for raw text",
+            ))),
+        })
+        .collect()
+}
+
+// Normalizes and pre-tokenizes a sequence like `TokenizerImpl::encode`,
+// returning the words that are left for the model to tokenize
+fn pre_tokenize_sequence(
+    tokenizer: &ExTokenizerImpl,
+    sequence: &str,
+) -> Result<(PreTokenizedString, Vec<String>), ExTokenizersError> {
+    let mut pretokenized = tokenizer
+        .get_added_vocabulary()
+        .extract_and_normalize(tokenizer.get_normalizer(), sequence);
+    if let Some(pre_tokenizer) = tokenizer.get_pre_tokenizer() {
+        pre_tokenizer.pre_tokenize(&mut pretokenized)?;
+    }
+    let words = pretokenized
+        .get_splits(OffsetReferential::Normalized, OffsetType::Byte)
+        .into_iter()
+        .filter(|(_, _, tokens)| tokens.is_none())
+        .map(|(text, _, _)| text.to_string())
+        .collect();
+    Ok((pretokenized, words))
+}
+
+// Builds the encoding of a pre-tokenized sequence, given the tokens of
+// each of its words
+fn segmented_encoding(
+    mut pretokenized: PreTokenizedString,
+    words: Vec<Vec<Token>>,
+    type_id: u32,
+) -> Result<Encoding, ExTokenizersError> {
+    let words = std::cell::RefCell::new(words.into_iter());
+    pretokenized.tokenize(|_| Ok(words.borrow_mut().next().unwrap_or_default()))?;
+    Ok(pretokenized.into_encoding(None, type_id, OffsetType::Byte)?)
+}
+
+// Encodes the input like `TokenizerImpl::encode`, but with every word
+// segmented at random by `segment`
+fn encode_randomized(
+    tokenizer: &ExTokenizerImpl,
+    sequence_tokenizers: &[ExTokenizerImpl; 2],
+    segment: &SegmentFn,
+    input: EncodeInput,
+    encode_special_tokens: (bool, bool),
+    add_special_tokens: bool,
+    rng: &mut Rng,
+) -> Result<Encoding, ExTokenizersError> {
+    let flags = [encode_special_tokens.0, encode_special_tokens.1];
+    let mut encodings = Vec::new();
+    for (index, sequence) in raw_sequences(&input)?.into_iter().enumerate() {
+        let (pretokenized, words) =
+            pre_tokenize_sequence(&sequence_tokenizers[flags[index] as usize], sequence)?;
+        let words = words
+            .iter()
+            .map(|word| segment(word, rng))
+            .collect::<tokenizers::Result<Vec<Vec<Token>>>>()?;
+        encodings.push(segmented_encoding(pretokenized, words, index as u32)?);
+    }
+    let mut encodings = encodings.into_iter();
+    let encoding = encodings.next().unwrap_or_default();
+    Ok(tokenizer.post_process(encoding, encodings.next(), add_special_tokens)?)
+}
+
+// Extends every candidate with every item of `next`, keeping the `n`
+// combinations with the highest score
+fn combine_nbest<T: Clone>(
+    candidates: Vec<(Vec<T>, f64)>,
+    next: &[(T, f64)],
+    n: usize,
+) -> Vec<(Vec<T>, f64)> {
+    let mut combined: Vec<(Vec<T>, f64)> = candidates
+        .iter()
+        .flat_map(|(items, score)| {
+            next.iter().map(move |(item, item_score)| {
+                let mut items = items.clone();
+                items.push(item.clone());
+                (items, score + item_score)
+            })
+        })
+        .collect();
+    combined.sort_by(|a, b| b.1.total_cmp(&a.1));
+    combined.truncate(n);
+    combined
+}
+
+#[derive(rustler::NifUnitEnum, Clone, Copy, PartialEq)]
+pub enum OverflowingMode {
+    Nested,
+    Flat,
+    Binaries,
+}
+
+#[derive(rustler::NifMap)]
+pub struct FlatEncodings {
+    encodings: Vec<ExTokenizersEncoding>,
+    overflow_to_sample_mapping: Vec<u32>,
+    window_index: Vec<u32>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct FlatBinaries<'a> {
+    ids: Binary<'a>,
+    type_ids: Binary<'a>,
+    attention_mask: Binary<'a>,
+    special_tokens_mask: Binary<'a>,
+    overflow_to_sample_mapping: Binary<'a>,
+    window_index: Binary<'a>,
+    shape: (usize, usize),
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum EncodeOutput<'a> {
+    Encoding(ExTokenizersEncoding),
+    Encodings(Vec<ExTokenizersEncoding>),
+    Flat(FlatEncodings),
+    Binaries(FlatBinaries<'a>),
+}
+
+// Flattens the overflowing windows of every encoding into a single list,
+// the way `return_overflowing_tokens` does in the Python library.
+fn flatten_overflowing(encodings: Vec<Encoding>) -> (Vec<Encoding>, Vec<u32>, Vec<u32>) {
+    let mut windows = Vec::with_capacity(encodings.len());
+    let mut overflow_to_sample_mapping = Vec::with_capacity(encodings.len());
+    let mut window_index = Vec::with_capacity(encodings.len());
+    for (sample, mut encoding) in encodings.into_iter().enumerate() {
+        let overflowing = encoding.take_overflowing();
+        for (index, window) in std::iter::once(encoding).chain(overflowing).enumerate() {
+            windows.push(window);
+            overflow_to_sample_mapping.push(sample as u32);
+            window_index.push(index as u32);
+        }
+    }
+    (windows, overflow_to_sample_mapping, window_index)
+}
+
+fn encode_output(
+    env: Env,
+    encodings: Vec<Encoding>,
+    mode: OverflowingMode,
+) -> Result<EncodeOutput, ExTokenizersError> {
+    if mode == OverflowingMode::Nested {
+        return Ok(EncodeOutput::Encodings(
+            encodings
+                .into_iter()
+                .map(|encoding| encoding.into())
+                .collect(),
+        ));
+    }
+
+    let (windows, overflow_to_sample_mapping, window_index) = flatten_overflowing(encodings);
+
+    if mode == OverflowingMode::Flat {
+        return Ok(EncodeOutput::Flat(FlatEncodings {
+            encodings: windows.into_iter().map(|window| window.into()).collect(),
+            overflow_to_sample_mapping,
+            window_index,
+        }));
+    }
+
+    let length = windows.first().map(|window| window.len()).unwrap_or(0);
+    if windows.iter().any(|window| window.len() != length) {
+        return Err(ExTokenizersError::Other(String::from(
+            "all windows must have the same length to be returned as binaries, configure padding to fix this",
+        )));
+    }
+    let concat = |field: fn(&Encoding) -> &[u32]| {
+        let values: Vec<u32> = windows.iter().flat_map(field).copied().collect();
+        u32_binary(env, &values)
+    };
+    Ok(EncodeOutput::Binaries(FlatBinaries {
+        ids: concat(Encoding::get_ids),
+        type_ids: concat(Encoding::get_type_ids),
+        attention_mask: concat(Encoding::get_attention_mask),
+        special_tokens_mask: concat(Encoding::get_special_tokens_mask),
+        overflow_to_sample_mapping: u32_binary(env, &overflow_to_sample_mapping),
+        window_index: u32_binary(env, &window_index),
+        shape: (windows.len(), length),
+    }))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn tokenizer_encode(
+pub fn tokenizer_encode<'a>(
+    env: Env<'a>,
     tokenizer: ExTokenizersTokenizer,
-    input: Term,
+    input: Term<'a>,
     options: Vec<EncodeOption>,
-) -> Result<ExTokenizersEncoding, ExTokenizersError> {
+) -> Result<EncodeOutput<'a>, ExTokenizersError> {
     struct Opts {
         add_special_tokens: bool,
         encoding_transformations: Vec<TransformationElement>,
+        overflowing: OverflowingMode,
+        encode_special_tokens: Option<EncodeSpecialTokens>,
+        protected_spans: Vec<ProtectedSpan>,
+        random_segmentation: Option<(RandomSegmentation, u64)>,
     }
     let mut opts = Opts {
         add_special_tokens: true,
         encoding_transformations: Vec::new(),
+        overflowing: OverflowingMode::Nested,
+        encode_special_tokens: None,
+        protected_spans: Vec::new(),
+        random_segmentation: None,
     };
     options.into_iter().for_each(|option| match option {
         EncodeOption::AddSpecialTokens(add_special_tokens) => {
@@ -455,31 +1097,88 @@ pub fn tokenizer_encode(
         EncodeOption::EncodingTransformations(encoding_transformations) => {
             opts.encoding_transformations = encoding_transformations
         }
+        EncodeOption::Overflowing(overflowing) => opts.overflowing = overflowing,
+        EncodeOption::EncodeSpecialTokens(encode_special_tokens) => {
+            opts.encode_special_tokens = Some(encode_special_tokens)
+        }
+        EncodeOption::ProtectedSpans(protected_spans) => opts.protected_spans = protected_spans,
+        EncodeOption::Sampling(sampling) => {
+            opts.random_segmentation = Some(sampling_params(sampling))
+        }
+        EncodeOption::Dropout(dropout) => opts.random_segmentation = Some(dropout_params(dropout)),
     });
+    if opts.random_segmentation.is_some() && !opts.protected_spans.is_empty() {
+        return Err(ExTokenizersError::Other(String::from(
+            "protected spans cannot be combined with :sampling or :dropout",
+        )));
+    }
 
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
     let input = term_to_encode_input(&input)?;
-    let mut encoding = tokenizer
-        .resource
-        .0
-        .encode(input, opts.add_special_tokens)?;
+    let encode_special_tokens = per_sequence(&opts.encode_special_tokens, tokenizer)?;
+    let mut encoding = match opts.random_segmentation {
+        Some((segmentation, seed)) => {
+            let variants = resource.variants()?;
+            with_random_segmentation(tokenizer, segmentation, |segment| {
+                encode_randomized(
+                    tokenizer,
+                    &variants.sequence,
+                    segment,
+                    input,
+                    encode_special_tokens,
+                    opts.add_special_tokens,
+                    &mut Rng::new(seed),
+                )
+            })?
+        }
+        None if opts.protected_spans.is_empty()
+            && encode_special_tokens.0 == encode_special_tokens.1 =>
+        {
+            with_encode_special_tokens(resource, encode_special_tokens.0, |tokenizer| {
+                Ok(tokenizer.encode(input, opts.add_special_tokens)?)
+            })?
+        }
+        None => encode_per_sequence(
+            tokenizer,
+            &*resource.variants()?,
+            input,
+            encode_special_tokens,
+            &opts.protected_spans,
+            opts.add_special_tokens,
+        )?,
+    };
     apply_transformations(&mut encoding, &opts.encoding_transformations);
-    Ok(encoding.into())
+
+    match opts.overflowing {
+        OverflowingMode::Nested => Ok(EncodeOutput::Encoding(encoding.into())),
+        mode => encode_output(env, vec![encoding], mode),
+    }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn tokenizer_encode_batch(
+pub fn tokenizer_encode_batch<'a>(
+    env: Env<'a>,
     tokenizer: ExTokenizersTokenizer,
-    inputs: Vec<Term>,
+    inputs: Vec<Term<'a>>,
     options: Vec<EncodeOption>,
     // add_special_tokens: bool,
-) -> Result<Vec<ExTokenizersEncoding>, ExTokenizersError> {
+) -> Result<EncodeOutput<'a>, ExTokenizersError> {
     struct Opts {
         add_special_tokens: bool,
         encoding_transformations: Vec<TransformationElement>,
+        overflowing: OverflowingMode,
+        encode_special_tokens: Option<EncodeSpecialTokens>,
+        protected_spans: Vec<ProtectedSpan>,
+        random_segmentation: Option<(RandomSegmentation, u64)>,
     }
     let mut opts = Opts {
         add_special_tokens: true,
         encoding_transformations: Vec::new(),
+        overflowing: OverflowingMode::Nested,
+        encode_special_tokens: None,
+        protected_spans: Vec::new(),
+        random_segmentation: None,
     };
     options.into_iter().for_each(|option| match option {
         EncodeOption::AddSpecialTokens(add_special_tokens) => {
@@ -488,101 +1187,1061 @@ pub fn tokenizer_encode_batch(
         EncodeOption::EncodingTransformations(encoding_transformations) => {
             opts.encoding_transformations = encoding_transformations
         }
+        EncodeOption::Overflowing(overflowing) => opts.overflowing = overflowing,
+        EncodeOption::EncodeSpecialTokens(encode_special_tokens) => {
+            opts.encode_special_tokens = Some(encode_special_tokens)
+        }
+        EncodeOption::ProtectedSpans(protected_spans) => opts.protected_spans = protected_spans,
+        EncodeOption::Sampling(sampling) => {
+            opts.random_segmentation = Some(sampling_params(sampling))
+        }
+        EncodeOption::Dropout(dropout) => opts.random_segmentation = Some(dropout_params(dropout)),
     });
+    if opts.random_segmentation.is_some() && !opts.protected_spans.is_empty() {
+        return Err(ExTokenizersError::Other(String::from(
+            "protected spans cannot be combined with :sampling or :dropout",
+        )));
+    }
+    if opts
+        .protected_spans
+        .iter()
+        .any(|span| matches!(span, ProtectedSpan::Span(_) | ProtectedSpan::SpanId(_)))
+    {
+        return Err(ExTokenizersError::Other(String::from(
+            "only literal protected spans are supported when encoding a batch",
+        )));
+    }
     let inputs = inputs
         .iter()
         .map(term_to_encode_input)
         .collect::<Result<Vec<EncodeInput>, ExTokenizersError>>()?;
-    let mut encodings = tokenizer
-        .resource
-        .0
-        .encode_batch(inputs, opts.add_special_tokens)?;
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
+    let encode_special_tokens = per_sequence(&opts.encode_special_tokens, tokenizer)?;
+    let mut encodings = match opts.random_segmentation {
+        Some((segmentation, seed)) => {
+            // Every row gets its own random stream, so that its result does
+            // not depend on the other rows of the batch
+            let variants = resource.variants()?;
+            let mut encodings = with_random_segmentation(tokenizer, segmentation, |segment| {
+                inputs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(row, input)| {
+                        encode_randomized(
+                            &variants.unpadded,
+                            &variants.sequence,
+                            segment,
+                            input,
+                            encode_special_tokens,
+                            opts.add_special_tokens,
+                            &mut Rng::for_row(seed, row),
+                        )
+                    })
+                    .collect::<Result<Vec<Encoding>, ExTokenizersError>>()
+            })?;
+            if let Some(params) = tokenizer.get_padding() {
+                pad_encodings(&mut encodings, params)?;
+            }
+            encodings
+        }
+        None if opts.protected_spans.is_empty()
+            && encode_special_tokens.0 == encode_special_tokens.1 =>
+        {
+            with_encode_special_tokens(resource, encode_special_tokens.0, |tokenizer| {
+                Ok(tokenizer.encode_batch(inputs, opts.add_special_tokens)?)
+            })?
+        }
+        None => {
+            // Padding applies to the whole batch, so it is done once all
+            // the inputs are encoded
+            let variants = resource.variants()?;
+            let mut encodings = inputs
+                .into_maybe_par_iter()
+                .map(|input| {
+                    encode_per_sequence(
+                        &variants.unpadded,
+                        &variants,
+                        input,
+                        encode_special_tokens,
+                        &opts.protected_spans,
+                        opts.add_special_tokens,
+                    )
+                })
+                .collect::<Result<Vec<Encoding>, ExTokenizersError>>()?;
+            if let Some(params) = tokenizer.get_padding() {
+                pad_encodings(&mut encodings, params)?;
+            }
+            encodings
+        }
+    };
 
     // Applying transformations (if any)
     for encoding in encodings.iter_mut() {
         apply_transformations(encoding, &opts.encoding_transformations);
     }
 
-    let ex_encodings = encodings
-        .into_iter()
-        .map(|encoding| encoding.into())
-        .collect();
-    Ok(ex_encodings)
+    encode_output(env, encodings, opts.overflowing)
 }
 
 #[derive(NifTaggedEnum)]
-pub enum DecodeOption {
-    SkipSpecialTokens(bool),
+pub enum NbestOption {
+    AddSpecialTokens(bool),
+    EncodeSpecialTokens(EncodeSpecialTokens),
+}
+
+#[derive(rustler::NifMap)]
+pub struct ScoredEncoding {
+    encoding: ExTokenizersEncoding,
+    score: f64,
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn tokenizer_decode(
+pub fn tokenizer_encode_nbest(
     tokenizer: ExTokenizersTokenizer,
-    ids: Vec<u32>,
-    options: Vec<DecodeOption>,
-) -> Result<String, ExTokenizersError> {
+    input: Term,
+    n: usize,
+    options: Vec<NbestOption>,
+) -> Result<Vec<ScoredEncoding>, ExTokenizersError> {
     struct Opts {
-        skip_special_tokens: bool,
+        add_special_tokens: bool,
+        encode_special_tokens: Option<EncodeSpecialTokens>,
     }
     let mut opts = Opts {
-        skip_special_tokens: true,
+        add_special_tokens: true,
+        encode_special_tokens: None,
     };
-    options.into_iter().for_each(|option| match option {
-        DecodeOption::SkipSpecialTokens(skip_special_tokens) => {
-            opts.skip_special_tokens = skip_special_tokens
+    for option in options {
+        match option {
+            NbestOption::AddSpecialTokens(value) => opts.add_special_tokens = value,
+            NbestOption::EncodeSpecialTokens(value) => opts.encode_special_tokens = Some(value),
         }
-    });
+    }
+    if n == 0 {
+        return Err(ExTokenizersError::Other(String::from(
+            "the number of segmentations must be positive",
+        )));
+    }
 
-    Ok(tokenizer
-        .resource
-        .0
-        .decode(&ids, opts.skip_special_tokens)?)
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
+    let input = term_to_encode_input(&input)?;
+    let encode_special_tokens = per_sequence(&opts.encode_special_tokens, tokenizer)?;
+    let flags = [encode_special_tokens.0, encode_special_tokens.1];
+    let variants = resource.variants()?;
+    let sequence_tokenizers = &variants.sequence;
+
+    // The best segmentations of each sequence are found by combining the
+    // best segmentations of its words, and then the sequences themselves
+    // are combined the same way
+    let candidates = with_unigram(tokenizer, |segmenter| {
+        let mut candidates: Vec<(Vec<Encoding>, f64)> = vec![(Vec::new(), 0.0)];
+        for (index, sequence) in raw_sequences(&input)?.into_iter().enumerate() {
+            let (pretokenized, words) =
+                pre_tokenize_sequence(&sequence_tokenizers[flags[index] as usize], sequence)?;
+            let mut segmentations: Vec<(Vec<Vec<Token>>, f64)> = vec![(Vec::new(), 0.0)];
+            for word in &words {
+                segmentations = combine_nbest(segmentations, &segmenter.nbest(word, n)?, n);
+            }
+            let encodings = segmentations
+                .into_iter()
+                .map(|(words, score)| {
+                    Ok((
+                        segmented_encoding(pretokenized.clone(), words, index as u32)?,
+                        score,
+                    ))
+                })
+                .collect::<Result<Vec<(Encoding, f64)>, ExTokenizersError>>()?;
+            candidates = combine_nbest(candidates, &encodings, n);
+        }
+        Ok(candidates)
+    })?;
+
+    candidates
+        .into_iter()
+        .map(|(encodings, score)| {
+            let mut encodings = encodings.into_iter();
+            let encoding = encodings.next().unwrap_or_default();
+            let encoding =
+                tokenizer.post_process(encoding, encodings.next(), opts.add_special_tokens)?;
+            Ok(ScoredEncoding {
+                encoding: encoding.into(),
+                score,
+            })
+        })
+        .collect()
+}
+
+#[derive(NifTaggedEnum)]
+pub enum BucketOption {
+    AddSpecialTokens(bool),
+    MaxBatchSize(usize),
+    PadToMultipleOf(usize),
+    ReturnEncodings(bool),
+}
+
+#[derive(rustler::NifMap)]
+pub struct Buckets {
+    batches: Option<Vec<Vec<ExTokenizersEncoding>>>,
+    indices: Vec<Vec<usize>>,
+    batch_lengths: Vec<usize>,
+    restore_order: Vec<usize>,
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn tokenizer_decode_batch(
+pub fn tokenizer_encode_bucketed(
     tokenizer: ExTokenizersTokenizer,
-    sentences: Vec<Vec<u32>>,
-    options: Vec<DecodeOption>,
-) -> Result<Vec<String>, ExTokenizersError> {
+    inputs: Vec<Term>,
+    max_tokens: usize,
+    options: Vec<BucketOption>,
+) -> Result<Buckets, ExTokenizersError> {
     struct Opts {
-        skip_special_tokens: bool,
+        add_special_tokens: bool,
+        max_batch_size: Option<usize>,
+        pad_to_multiple_of: Option<usize>,
+        return_encodings: bool,
     }
+    let padding = tokenizer.resource.0.get_padding().cloned();
     let mut opts = Opts {
-        skip_special_tokens: true,
+        add_special_tokens: true,
+        max_batch_size: None,
+        pad_to_multiple_of: padding.as_ref().and_then(|p| p.pad_to_multiple_of),
+        return_encodings: true,
     };
     options.into_iter().for_each(|option| match option {
-        DecodeOption::SkipSpecialTokens(skip_special_tokens) => {
-            opts.skip_special_tokens = skip_special_tokens
+        BucketOption::AddSpecialTokens(add_special_tokens) => {
+            opts.add_special_tokens = add_special_tokens
         }
+        BucketOption::MaxBatchSize(max_batch_size) => opts.max_batch_size = Some(max_batch_size),
+        BucketOption::PadToMultipleOf(multiple) => opts.pad_to_multiple_of = Some(multiple),
+        BucketOption::ReturnEncodings(return_encodings) => opts.return_encodings = return_encodings,
     });
 
-    Ok(tokenizer.resource.0.decode_batch(
-        sentences
+    let inputs = inputs
+        .iter()
+        .map(term_to_encode_input)
+        .collect::<Result<Vec<EncodeInput>, ExTokenizersError>>()?;
+
+    // Every batch is padded separately below, so the tokenizer padding
+    // must not pad the whole input to its longest sequence
+    let mut encodings = tokenizer
+        .resource
+        .variants()?
+        .unpadded
+        .encode_batch(inputs, opts.add_special_tokens)?
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+
+    let padded_length = |length: usize| match opts.pad_to_multiple_of {
+        Some(multiple) if multiple > 0 => length.div_ceil(multiple) * multiple,
+        _ => length,
+    };
+
+    let mut order: Vec<usize> = (0..encodings.len()).collect();
+    order.sort_by_key(|&index| encodings[index].as_ref().unwrap().len());
+
+    // Since inputs are sorted by length, the last one added to a batch is
+    // always the longest
+    let mut indices: Vec<Vec<usize>> = Vec::new();
+    let mut batch_lengths: Vec<usize> = Vec::new();
+    for index in order {
+        let length = padded_length(encodings[index].as_ref().unwrap().len());
+        let fits = match indices.last() {
+            Some(batch) => {
+                (batch.len() + 1) * length <= max_tokens
+                    && opts.max_batch_size.is_none_or(|size| batch.len() < size)
+            }
+            None => false,
+        };
+        if fits {
+            indices.last_mut().unwrap().push(index);
+            *batch_lengths.last_mut().unwrap() = length;
+        } else {
+            indices.push(vec![index]);
+            batch_lengths.push(length);
+        }
+    }
+
+    let mut restore_order = vec![0; encodings.len()];
+    for (position, &index) in indices.iter().flatten().enumerate() {
+        restore_order[index] = position;
+    }
+
+    let batches = opts.return_encodings.then(|| {
+        let padding = padding.unwrap_or_default();
+        indices
             .iter()
-            .map(Vec::as_slice)
-            .collect::<Vec<&[u32]>>()
-            .as_slice(),
-        opts.skip_special_tokens,
-    )?)
+            .zip(&batch_lengths)
+            .map(|(batch, &length)| {
+                batch
+                    .iter()
+                    .map(|&index| {
+                        let mut encoding = encodings[index].take().unwrap();
+                        encoding.pad(
+                            length,
+                            padding.pad_id,
+                            padding.pad_type_id,
+                            &padding.pad_token,
+                            padding.direction,
+                        );
+                        encoding.into()
+                    })
+                    .collect()
+            })
+            .collect()
+    });
+
+    Ok(Buckets {
+        batches,
+        indices,
+        batch_lengths,
+        restore_order,
+    })
 }
 
-#[rustler::nif]
-pub fn tokenizer_token_to_id(tokenizer: ExTokenizersTokenizer, token: &str) -> Option<u32> {
-    tokenizer.resource.0.token_to_id(token)
+#[derive(NifUntaggedEnum)]
+pub enum ChatSegment {
+    Turn((String, String)),
+    TrainableTurn((String, String, bool)),
 }
 
-#[rustler::nif]
-pub fn tokenizer_id_to_token(tokenizer: ExTokenizersTokenizer, id: u32) -> Option<String> {
-    tokenizer.resource.0.id_to_token(id)
+#[derive(NifTaggedEnum)]
+pub enum ChatOption {
+    AddSpecialTokens(bool),
+    TrainableRoles(Vec<String>),
+    Templates(HashMap<String, (String, String)>),
+    MaxLength(Option<usize>),
+    KeepFirst(usize),
+    IgnoreIndex(i32),
+    EncodeSpecialTokens(ChatSpecialTokens),
 }
 
-#[rustler::nif]
-pub fn tokenizer_post_processing(
-    tokenizer: ExTokenizersTokenizer,
-    enc: ExTokenizersEncoding,
-    pair: Option<ExTokenizersEncoding>,
-    add_special_tokens: bool,
+#[derive(NifUntaggedEnum)]
+pub enum ChatSpecialTokens {
+    All(bool),
+    Roles(Vec<String>),
+}
+
+#[derive(rustler::NifMap)]
+pub struct ChatTurn {
+    segment: usize,
+    role: String,
+    start: usize,
+    end: usize,
+}
+
+#[derive(rustler::NifMap)]
+pub struct ChatEncoding<'a> {
+    encoding: ExTokenizersEncoding,
+    loss_mask: Binary<'a>,
+    labels: Binary<'a>,
+    turns: Vec<ChatTurn>,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_encode_chat<'a>(
+    env: Env<'a>,
+    tokenizer: ExTokenizersTokenizer,
+    segments: Vec<ChatSegment>,
+    options: Vec<ChatOption>,
+) -> Result<ChatEncoding<'a>, ExTokenizersError> {
+    struct Opts {
+        add_special_tokens: bool,
+        trainable_roles: Vec<String>,
+        templates: HashMap<String, (String, String)>,
+        max_length: Option<usize>,
+        keep_first: usize,
+        ignore_index: i32,
+        encode_special_tokens: ChatSpecialTokens,
+    }
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
+    let mut opts = Opts {
+        add_special_tokens: true,
+        trainable_roles: vec![String::from("assistant")],
+        templates: HashMap::new(),
+        max_length: tokenizer.get_truncation().map(|params| params.max_length),
+        keep_first: 0,
+        ignore_index: -100,
+        encode_special_tokens: ChatSpecialTokens::All(tokenizer.get_encode_special_tokens()),
+    };
+    for option in options {
+        match option {
+            ChatOption::AddSpecialTokens(add_special_tokens) => {
+                opts.add_special_tokens = add_special_tokens
+            }
+            ChatOption::TrainableRoles(roles) => opts.trainable_roles = roles,
+            ChatOption::Templates(templates) => opts.templates = templates,
+            ChatOption::MaxLength(max_length) => opts.max_length = max_length,
+            ChatOption::KeepFirst(keep_first) => opts.keep_first = keep_first,
+            ChatOption::IgnoreIndex(ignore_index) => opts.ignore_index = ignore_index,
+            ChatOption::EncodeSpecialTokens(encode_special_tokens) => {
+                opts.encode_special_tokens = encode_special_tokens
+            }
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(ExTokenizersError::Other(String::from(
+            "expected at least one segment",
+        )));
+    }
+
+    // Truncation and padding apply to the whole conversation below
+    let variants = resource.variants()?;
+
+    // Every turn is encoded on its own, wrapped by the role template. The
+    // template prefix never counts towards the loss, while the suffix (usually
+    // the end of turn token) does for trainable turns. Templates always match
+    // special tokens, while the text may encode them as ordinary text. As
+    // with an encoding of the whole turn, only its first part gets a prefix
+    // space, and the offsets of each part follow its position in the turn
+    let mut turns = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let (role, text, trainable) = match segment {
+            ChatSegment::Turn((role, text)) => {
+                let trainable = opts.trainable_roles.contains(&role);
+                (role, text, trainable)
+            }
+            ChatSegment::TrainableTurn(turn) => turn,
+        };
+        let (prefix, suffix) = opts.templates.get(&role).cloned().unwrap_or_default();
+        let sanitized = match &opts.encode_special_tokens {
+            ChatSpecialTokens::All(value) => *value,
+            ChatSpecialTokens::Roles(roles) => roles.contains(&role),
+        };
+        let mut encoding = Encoding::default();
+        let mut mask = Vec::new();
+        let mut length = 0;
+        let mut words = 0;
+        for (part, part_trainable, part_sanitized) in [
+            (prefix, false, false),
+            (text, trainable, sanitized),
+            (suffix, trainable, false),
+        ] {
+            if part.is_empty() {
+                continue;
+            }
+            let part_tokenizers = match length {
+                0 => &variants.sequence,
+                _ => &variants.continuation,
+            };
+            let part_encoding =
+                part_tokenizers[part_sanitized as usize].encode(part.as_str(), false)?;
+            mask.extend(std::iter::repeat_n(
+                part_trainable as u32,
+                part_encoding.len(),
+            ));
+            merge_shifted(&mut encoding, part_encoding, length, &mut words);
+            length += part.len();
+        }
+        turns.push((role, encoding, mask, length));
+    }
+
+    let n_added_tokens = match tokenizer.get_post_processor() {
+        Some(processor) if opts.add_special_tokens => processor.added_tokens(false),
+        _ => 0,
+    };
+
+    // Whole turns are dropped from the start, after the first `keep_first`
+    // ones, until the conversation fits. The last turn is never dropped, it is
+    // truncated instead when it does not fit on its own
+    let mut kept: Vec<usize> = (0..turns.len()).collect();
+    let mut max_tokens = None;
+    if let Some(max_length) = opts.max_length {
+        let max_length = max_length.saturating_sub(n_added_tokens);
+        let mut total: usize = turns.iter().map(|(_, encoding, _, _)| encoding.len()).sum();
+        while total > max_length && kept.len() > opts.keep_first + 1 {
+            total -= turns[kept.remove(opts.keep_first)].1.len();
+        }
+        max_tokens = Some(max_length);
+    }
+
+    // The kept turns follow each other, so their offsets are shifted to be
+    // relative to the concatenation of their text
+    let mut spans = Vec::with_capacity(kept.len());
+    let mut encoding = Encoding::default();
+    let mut mask = Vec::new();
+    let mut length = 0;
+    let mut words = 0;
+    for index in kept {
+        let (role, turn_encoding, turn_mask, turn_length) = std::mem::take(&mut turns[index]);
+        spans.push(ChatTurn {
+            segment: index,
+            role,
+            start: mask.len(),
+            end: mask.len() + turn_mask.len(),
+        });
+        merge_shifted(&mut encoding, turn_encoding, length, &mut words);
+        mask.extend(turn_mask);
+        length += turn_length;
+    }
+    if let Some(max_tokens) = max_tokens {
+        encoding.truncate(max_tokens, 0, TruncationDirection::Right);
+        encoding.take_overflowing();
+        mask.truncate(max_tokens);
+        spans.retain(|span| span.start < max_tokens);
+        spans
+            .iter_mut()
+            .for_each(|span| span.end = span.end.min(max_tokens));
+    }
+
+    let mut encoding = match tokenizer.get_post_processor() {
+        Some(processor) => processor.process(encoding, None, opts.add_special_tokens)?,
+        None => <dyn PostProcessor>::default_process(vec![encoding], opts.add_special_tokens)?
+            .pop()
+            .unwrap(),
+    };
+    if let Some(params) = tokenizer.get_padding() {
+        pad_encodings(std::slice::from_mut(&mut encoding), params)?;
+    }
+
+    // Post-processing and padding only add tokens around the conversation,
+    // which are the ones outside of the first sequence
+    let sequence_ids = encoding.get_sequence_ids();
+    let offset = sequence_ids
+        .iter()
+        .position(|sequence_id| *sequence_id == Some(0))
+        .unwrap_or(0);
+    spans.iter_mut().for_each(|span| {
+        span.start += offset;
+        span.end += offset;
+    });
+    let mut turn_mask = mask.into_iter();
+    let loss_mask: Vec<u32> = sequence_ids
+        .iter()
+        .map(|sequence_id| match sequence_id {
+            Some(0) => turn_mask.next().unwrap_or(0),
+            _ => 0,
+        })
+        .collect();
+    let labels: Vec<i32> = encoding
+        .get_ids()
+        .iter()
+        .zip(&loss_mask)
+        .map(|(id, mask)| {
+            if *mask == 1 {
+                *id as i32
+            } else {
+                opts.ignore_index
+            }
+        })
+        .collect();
+
+    Ok(ChatEncoding {
+        encoding: encoding.into(),
+        loss_mask: u32_binary(env, &loss_mask),
+        labels: i32_binary(env, &labels),
+        turns: spans,
+    })
+}
+
+#[derive(NifTaggedEnum)]
+pub enum MultiOption {
+    AddSpecialTokens(bool),
+    Template(String),
+    MaxLength(Option<usize>),
+    Budgets(Vec<Option<usize>>),
+    Priorities(Vec<i64>),
+    Direction(Direction),
+    EncodeSpecialTokens(EncodeSpecialTokens),
+}
+
+enum MultiPiece {
+    Sequence { index: usize, type_id: Option<u32> },
+    SpecialToken { token: String, type_id: Option<u32> },
+}
+
+// Parses a template in the same syntax as the template post-processor,
+// where sequences go from `$A` to `$Z` and an optional `:type_id` suffix
+// sets the type id of a piece
+fn parse_multi_template(template: &str) -> Result<Vec<MultiPiece>, ExTokenizersError> {
+    template
+        .split_whitespace()
+        .map(|piece| {
+            let (name, type_id) = match piece.rsplit_once(':') {
+                Some((name, type_id)) if !name.is_empty() => {
+                    let type_id = type_id.parse::<u32>().map_err(|_| {
+                        ExTokenizersError::InvalidTemplate(format!(
+                            "invalid type id in template piece {piece}"
+                        ))
+                    })?;
+                    (name, Some(type_id))
+                }
+                _ => (piece, None),
+            };
+            match name.strip_prefix('$') {
+                Some(sequence) => match sequence.as_bytes() {
+                    [letter @ b'A'..=b'Z'] => Ok(MultiPiece::Sequence {
+                        index: (letter - b'A') as usize,
+                        type_id,
+                    }),
+                    _ => Err(ExTokenizersError::InvalidTemplate(format!(
+                        "invalid sequence in template piece {piece}, expected $A to $Z"
+                    ))),
+                },
+                None => Ok(MultiPiece::SpecialToken {
+                    token: name.to_string(),
+                    type_id,
+                }),
+            }
+        })
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_encode_multi(
+    tokenizer: ExTokenizersTokenizer,
+    sequences: Vec<String>,
+    options: Vec<MultiOption>,
+) -> Result<ExTokenizersEncoding, ExTokenizersError> {
+    struct Opts {
+        add_special_tokens: bool,
+        template: Option<String>,
+        max_length: Option<usize>,
+        budgets: Vec<Option<usize>>,
+        priorities: Vec<i64>,
+        direction: TruncationDirection,
+        encode_special_tokens: EncodeSpecialTokens,
+    }
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
+    let truncation = tokenizer.get_truncation();
+    let mut opts = Opts {
+        add_special_tokens: true,
+        template: None,
+        max_length: truncation.map(|params| params.max_length),
+        budgets: Vec::new(),
+        priorities: Vec::new(),
+        direction: truncation
+            .map(|params| params.direction)
+            .unwrap_or(TruncationDirection::Right),
+        encode_special_tokens: EncodeSpecialTokens::All(tokenizer.get_encode_special_tokens()),
+    };
+    for option in options {
+        match option {
+            MultiOption::AddSpecialTokens(add_special_tokens) => {
+                opts.add_special_tokens = add_special_tokens
+            }
+            MultiOption::Template(template) => opts.template = Some(template),
+            MultiOption::MaxLength(max_length) => opts.max_length = max_length,
+            MultiOption::Budgets(budgets) => opts.budgets = budgets,
+            MultiOption::Priorities(priorities) => opts.priorities = priorities,
+            MultiOption::Direction(direction) => opts.direction = direction.into(),
+            MultiOption::EncodeSpecialTokens(encode_special_tokens) => {
+                opts.encode_special_tokens = encode_special_tokens
+            }
+        }
+    }
+
+    let pieces = match &opts.template {
+        Some(template) => parse_multi_template(template)?,
+        None => (0..sequences.len())
+            .map(|index| MultiPiece::Sequence {
+                index,
+                type_id: None,
+            })
+            .collect(),
+    };
+    let mut occurrences = vec![0; sequences.len()];
+    for piece in &pieces {
+        if let MultiPiece::Sequence { index, .. } = piece {
+            match occurrences.get_mut(*index) {
+                Some(count) => *count += 1,
+                None => {
+                    return Err(ExTokenizersError::Other(format!(
+                        "template refers to ${}, but only {} sequences were given",
+                        (b'A' + *index as u8) as char,
+                        sequences.len()
+                    )))
+                }
+            }
+        }
+    }
+    if occurrences.iter().any(|count| *count != 1) {
+        return Err(ExTokenizersError::Other(String::from(
+            "every sequence must appear exactly once in the template",
+        )));
+    }
+
+    // Special tokens are only added by the template below
+    let variants = resource.variants()?;
+    let sequence_tokenizers = &variants.sequence;
+    let mut encodings = sequences
+        .into_iter()
+        .enumerate()
+        .map(|(index, sequence)| {
+            let sanitized = opts.encode_special_tokens.for_sequence(index);
+            sequence_tokenizers[sanitized as usize].encode(sequence, false)
+        })
+        .collect::<tokenizers::Result<Vec<Encoding>>>()?;
+
+    let mut lengths: Vec<usize> = encodings
+        .iter()
+        .enumerate()
+        .map(|(index, encoding)| match opts.budgets.get(index) {
+            Some(Some(budget)) => encoding.len().min(*budget),
+            _ => encoding.len(),
+        })
+        .collect();
+
+    // Sequences with the lowest priority are truncated first and, among
+    // equal priorities, the later sequences are
+    if let Some(max_length) = opts.max_length {
+        let n_special_tokens = if opts.add_special_tokens {
+            pieces
+                .iter()
+                .filter(|piece| matches!(piece, MultiPiece::SpecialToken { .. }))
+                .count()
+        } else {
+            0
+        };
+        let max_length = max_length.saturating_sub(n_special_tokens);
+        let mut excess = lengths.iter().sum::<usize>().saturating_sub(max_length);
+        let mut order: Vec<usize> = (0..lengths.len()).collect();
+        order.sort_by_key(|&index| {
+            (
+                opts.priorities.get(index).copied().unwrap_or(0),
+                std::cmp::Reverse(index),
+            )
+        });
+        for index in order {
+            let cut = excess.min(lengths[index]);
+            lengths[index] -= cut;
+            excess -= cut;
+        }
+    }
+
+    let mut type_id = 0;
+    let mut parts = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        match piece {
+            MultiPiece::Sequence {
+                index,
+                type_id: piece_type_id,
+            } => {
+                let mut encoding = std::mem::take(&mut encodings[index]);
+                encoding.truncate(lengths[index], 0, opts.direction);
+                encoding.take_overflowing();
+                type_id = piece_type_id.unwrap_or(index as u32);
+                encoding.set_type_ids(vec![type_id; encoding.len()]);
+                encoding.set_sequence_id(index);
+                parts.push(encoding);
+            }
+            MultiPiece::SpecialToken {
+                token,
+                type_id: piece_type_id,
+            } => {
+                if !opts.add_special_tokens {
+                    continue;
+                }
+                let id = tokenizer.token_to_id(&token).ok_or_else(|| {
+                    ExTokenizersError::UnknownToken(format!(
+                        "unknown special token {token} in template"
+                    ))
+                })?;
+                parts.push(Encoding::new(
+                    vec![id],
+                    vec![piece_type_id.unwrap_or(type_id)],
+                    vec![token],
+                    vec![None],
+                    vec![(0, 0)],
+                    vec![1],
+                    vec![1],
+                    Vec::new(),
+                    HashMap::new(),
+                ));
+            }
+        }
+    }
+
+    let mut encoding = Encoding::merge(parts, false);
+    if let Some(params) = tokenizer.get_padding() {
+        pad_encodings(std::slice::from_mut(&mut encoding), params)?;
+    }
+    Ok(encoding.into())
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum PlaceholderExpansion {
+    Count(usize),
+    Ids(Vec<u32>),
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum PlaceholderSpec {
+    Each(usize),
+    PerOccurrence(Vec<PlaceholderExpansion>),
+}
+
+#[derive(NifTaggedEnum)]
+pub enum PlaceholderOption {
+    AddSpecialTokens(bool),
+}
+
+#[derive(rustler::NifMap)]
+pub struct MediaEncoding<'a> {
+    encoding: ExTokenizersEncoding,
+    media_mask: Binary<'a>,
+}
+
+struct Placeholder {
+    token: String,
+    spec: PlaceholderSpec,
+    occurrences: usize,
+}
+
+// Expanded tokens keep the offsets of their placeholder, which are recorded
+// in `spans`, so that they can be found again once the sequence went
+// through truncation, post-processing and padding
+fn expand_placeholders(
+    tokenizer: &ExTokenizerImpl,
+    encoding: Encoding,
+    placeholders: &mut HashMap<u32, Placeholder>,
+    spans: &mut HashSet<(usize, usize)>,
+) -> Result<Encoding, ExTokenizersError> {
+    let mut expanded = Encoding::default();
+    for index in 0..encoding.len() {
+        let id = encoding.get_ids()[index];
+        let Some(placeholder) = placeholders.get_mut(&id) else {
+            let token = encoding.get_tokens()[index].clone();
+            expanded.merge_with(token_encoding(&encoding, index, id, token), false);
+            continue;
+        };
+        let ids = match &placeholder.spec {
+            PlaceholderSpec::Each(count) => vec![id; *count],
+            PlaceholderSpec::PerOccurrence(expansions) => {
+                match expansions.get(placeholder.occurrences) {
+                    Some(PlaceholderExpansion::Count(count)) => vec![id; *count],
+                    Some(PlaceholderExpansion::Ids(ids)) => ids.clone(),
+                    None => {
+                        return Err(ExTokenizersError::Other(format!(
+                            "found more occurrences of {} than expansions",
+                            placeholder.token
+                        )))
+                    }
+                }
+            }
+        };
+        placeholder.occurrences += 1;
+        spans.insert(encoding.get_offsets()[index]);
+        for id in ids {
+            let token = match tokenizer.id_to_token(id) {
+                Some(token) => token,
+                None => placeholder.token.clone(),
+            };
+            expanded.merge_with(token_encoding(&encoding, index, id, token), false);
+        }
+    }
+    Ok(expanded)
+}
+
+fn token_encoding(encoding: &Encoding, index: usize, id: u32, token: String) -> Encoding {
+    Encoding::new(
+        vec![id],
+        vec![encoding.get_type_ids()[index]],
+        vec![token],
+        vec![encoding.get_word_ids()[index]],
+        vec![encoding.get_offsets()[index]],
+        vec![encoding.get_special_tokens_mask()[index]],
+        vec![encoding.get_attention_mask()[index]],
+        Vec::new(),
+        HashMap::new(),
+    )
+}
+
+// Marks the tokens of each sequence whose offsets are the ones of an
+// expanded placeholder of that sequence
+fn placeholder_mask(encoding: &Encoding, spans: &[HashSet<(usize, usize)>]) -> Vec<u32> {
+    encoding
+        .get_sequence_ids()
+        .into_iter()
+        .zip(encoding.get_offsets())
+        .zip(encoding.get_special_tokens_mask())
+        .map(|((sequence_id, offsets), special)| {
+            let expanded = *special == 0
+                && sequence_id
+                    .and_then(|sequence_id| spans.get(sequence_id))
+                    .is_some_and(|spans| spans.contains(offsets));
+            expanded as u32
+        })
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_encode_with_placeholders<'a>(
+    env: Env<'a>,
+    tokenizer: ExTokenizersTokenizer,
+    input: Term<'a>,
+    placeholders: HashMap<String, PlaceholderSpec>,
+    options: Vec<PlaceholderOption>,
+) -> Result<MediaEncoding<'a>, ExTokenizersError> {
+    struct Opts {
+        add_special_tokens: bool,
+    }
+    let mut opts = Opts {
+        add_special_tokens: true,
+    };
+    for option in options {
+        match option {
+            PlaceholderOption::AddSpecialTokens(add_special_tokens) => {
+                opts.add_special_tokens = add_special_tokens
+            }
+        }
+    }
+
+    let resource = &tokenizer.resource;
+    let tokenizer = &resource.0;
+    let mut placeholders = placeholders
+        .into_iter()
+        .map(|(token, spec)| match tokenizer.token_to_id(&token) {
+            Some(id) => Ok((
+                id,
+                Placeholder {
+                    token,
+                    spec,
+                    occurrences: 0,
+                },
+            )),
+            None => Err(ExTokenizersError::UnknownToken(format!(
+                "placeholder {token} is not in the vocabulary"
+            ))),
+        })
+        .collect::<Result<HashMap<u32, Placeholder>, ExTokenizersError>>()?;
+
+    // Sequences are encoded on their own, so that placeholders are expanded
+    // before truncation, post-processing and padding. When special tokens
+    // are encoded as ordinary text, placeholders are still matched, as
+    // protected spans, and only the text around them is sanitized
+    let variants = resource.variants()?;
+    let encode_special_tokens = tokenizer.get_encode_special_tokens();
+    let protected_spans: Vec<ProtectedSpan> = if encode_special_tokens {
+        placeholders
+            .iter()
+            .map(|(id, placeholder)| ProtectedSpan::LiteralId((placeholder.token.clone(), *id)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let encode =
+        |sequence| encode_protected(&variants, encode_special_tokens, sequence, &protected_spans);
+    let (sequence, pair) = match term_to_encode_input(&input)? {
+        EncodeInput::Single(sequence) => (sequence, None),
+        EncodeInput::Dual(sequence, pair) => (sequence, Some(pair)),
+    };
+    let mut spans = vec![HashSet::new(), HashSet::new()];
+    let sequence = expand_placeholders(
+        tokenizer,
+        encode(sequence)?,
+        &mut placeholders,
+        &mut spans[0],
+    )?;
+    let pair = match pair {
+        Some(pair) => Some(expand_placeholders(
+            tokenizer,
+            encode(pair)?,
+            &mut placeholders,
+            &mut spans[1],
+        )?),
+        None => None,
+    };
+
+    for placeholder in placeholders.values() {
+        if let PlaceholderSpec::PerOccurrence(expansions) = &placeholder.spec {
+            if expansions.len() != placeholder.occurrences {
+                return Err(ExTokenizersError::Other(format!(
+                    "expected {} expansions for {}, found {} occurrences",
+                    expansions.len(),
+                    placeholder.token,
+                    placeholder.occurrences
+                )));
+            }
+        }
+    }
+
+    let encoding = tokenizer.post_process(sequence, pair, opts.add_special_tokens)?;
+    let media_mask = placeholder_mask(&encoding, &spans);
+
+    Ok(MediaEncoding {
+        encoding: encoding.into(),
+        media_mask: u32_binary(env, &media_mask),
+    })
+}
+
+#[derive(NifTaggedEnum)]
+pub enum DecodeOption {
+    SkipSpecialTokens(bool),
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_decode(
+    tokenizer: ExTokenizersTokenizer,
+    ids: Vec<u32>,
+    options: Vec<DecodeOption>,
+) -> Result<String, ExTokenizersError> {
+    struct Opts {
+        skip_special_tokens: bool,
+    }
+    let mut opts = Opts {
+        skip_special_tokens: true,
+    };
+    options.into_iter().for_each(|option| match option {
+        DecodeOption::SkipSpecialTokens(skip_special_tokens) => {
+            opts.skip_special_tokens = skip_special_tokens
+        }
+    });
+
+    Ok(tokenizer
+        .resource
+        .0
+        .decode(&ids, opts.skip_special_tokens)?)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_decode_batch(
+    tokenizer: ExTokenizersTokenizer,
+    sentences: Vec<Vec<u32>>,
+    options: Vec<DecodeOption>,
+) -> Result<Vec<String>, ExTokenizersError> {
+    struct Opts {
+        skip_special_tokens: bool,
+    }
+    let mut opts = Opts {
+        skip_special_tokens: true,
+    };
+    options.into_iter().for_each(|option| match option {
+        DecodeOption::SkipSpecialTokens(skip_special_tokens) => {
+            opts.skip_special_tokens = skip_special_tokens
+        }
+    });
+
+    Ok(tokenizer.resource.0.decode_batch(
+        sentences
+            .iter()
+            .map(Vec::as_slice)
+            .collect::<Vec<&[u32]>>()
+            .as_slice(),
+        opts.skip_special_tokens,
+    )?)
+}
+
+#[rustler::nif]
+pub fn tokenizer_token_to_id(tokenizer: ExTokenizersTokenizer, token: &str) -> Option<u32> {
+    tokenizer.resource.0.token_to_id(token)
+}
+
+#[rustler::nif]
+pub fn tokenizer_id_to_token(tokenizer: ExTokenizersTokenizer, id: u32) -> Option<String> {
+    tokenizer.resource.0.id_to_token(id)
+}
+
+#[rustler::nif]
+pub fn tokenizer_post_processing(
+    tokenizer: ExTokenizersTokenizer,
+    enc: ExTokenizersEncoding,
+    pair: Option<ExTokenizersEncoding>,
+    add_special_tokens: bool,
 ) -> Result<ExTokenizersEncoding, ExTokenizersError> {
     let result: tokenizers::Encoding = tokenizer.resource.0.post_process(
         enc.resource.0.clone(),
@@ -592,6 +2251,212 @@ pub fn tokenizer_post_processing(
     Ok(result.into())
 }
 
+#[derive(NifTaggedEnum)]
+pub enum TraceOption {
+    AddSpecialTokens(bool),
+}
+
+#[derive(rustler::NifMap)]
+pub struct TraceSplit {
+    text: String,
+    original: (usize, usize),
+    normalized: (usize, usize),
+    added_token: Option<u32>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct TraceNormalized {
+    normalized: String,
+    alignments: Vec<(usize, usize)>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct TraceModelSplit {
+    text: String,
+    tokens: Vec<String>,
+    ids: Vec<u32>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct SequenceTrace {
+    added_tokens: Vec<TraceSplit>,
+    normalized: TraceNormalized,
+    pre_tokens: Vec<TraceSplit>,
+    model: Vec<TraceModelSplit>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct TraceInsertion {
+    index: usize,
+    token: String,
+    id: u32,
+}
+
+#[derive(rustler::NifMap)]
+pub struct Trace {
+    sequences: Vec<SequenceTrace>,
+    post_processor: Vec<TraceInsertion>,
+    encoding: ExTokenizersEncoding,
+}
+
+// Splits with their offsets in the original string and in the whole
+// normalized string, found from the alignments
+fn trace_splits(
+    pretokenized: &PreTokenizedString,
+    alignments: &[(usize, usize)],
+) -> Vec<TraceSplit> {
+    pretokenized
+        .get_splits(OffsetReferential::Original, OffsetType::Byte)
+        .into_iter()
+        .map(|(text, (start, end), tokens)| TraceSplit {
+            text: text.to_string(),
+            original: (start, end),
+            normalized: (
+                alignments.partition_point(|alignment| alignment.0 < start),
+                alignments.partition_point(|alignment| alignment.0 < end),
+            ),
+            added_token: tokens
+                .as_ref()
+                .and_then(|tokens| tokens.first().map(|token| token.id)),
+        })
+        .collect()
+}
+
+// Runs the same steps as `TokenizerImpl::encode_single_sequence`, keeping
+// the intermediate results of every stage
+fn trace_sequence(
+    tokenizer: &ExTokenizerImpl,
+    sequence: &str,
+    type_id: u32,
+) -> Result<(SequenceTrace, Encoding), ExTokenizersError> {
+    let mut pretokenized = tokenizer
+        .get_added_vocabulary()
+        .extract_and_normalize(tokenizer.get_normalizer(), sequence);
+    let splits: Vec<(String, (usize, usize))> = pretokenized
+        .get_splits(OffsetReferential::Original, OffsetType::Byte)
+        .into_iter()
+        .map(|(text, offsets, _)| (text.to_string(), offsets))
+        .collect();
+
+    // Splitting without changes gives access to the normalized string of
+    // every split that is not an added token. The characters of added
+    // tokens are all aligned to the whole token
+    let mut split_alignments: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    pretokenized.split(|index, normalized| {
+        split_alignments.insert(index, normalized_alignments(&normalized));
+        Ok(vec![normalized])
+    })?;
+    let mut normalized = TraceNormalized {
+        normalized: String::new(),
+        alignments: Vec::new(),
+    };
+    for (index, (text, original)) in splits.into_iter().enumerate() {
+        match split_alignments.remove(&index) {
+            Some(alignments) => normalized.alignments.extend(alignments),
+            None => normalized
+                .alignments
+                .extend(std::iter::repeat_n(original, text.len())),
+        }
+        normalized.normalized.push_str(&text);
+    }
+    let added_tokens = trace_splits(&pretokenized, &normalized.alignments);
+
+    if let Some(pre_tokenizer) = tokenizer.get_pre_tokenizer() {
+        pre_tokenizer.pre_tokenize(&mut pretokenized)?;
+    }
+    let pre_tokens = trace_splits(&pretokenized, &normalized.alignments);
+
+    let model = tokenizer.get_model();
+    pretokenized.tokenize(|normalized| model.tokenize(normalized.get()))?;
+    let model = pretokenized
+        .get_splits(OffsetReferential::Original, OffsetType::Byte)
+        .into_iter()
+        .map(|(text, _, tokens)| {
+            let tokens = tokens.as_deref().unwrap_or_default();
+            TraceModelSplit {
+                text: text.to_string(),
+                tokens: tokens.iter().map(|token| token.value.clone()).collect(),
+                ids: tokens.iter().map(|token| token.id).collect(),
+            }
+        })
+        .collect();
+    let encoding = pretokenized.into_encoding(None, type_id, OffsetType::Byte)?;
+
+    Ok((
+        SequenceTrace {
+            added_tokens,
+            normalized,
+            pre_tokens,
+            model,
+        },
+        encoding,
+    ))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_trace(
+    tokenizer: ExTokenizersTokenizer,
+    input: Term,
+    options: Vec<TraceOption>,
+) -> Result<Trace, ExTokenizersError> {
+    struct Opts {
+        add_special_tokens: bool,
+    }
+    let mut opts = Opts {
+        add_special_tokens: true,
+    };
+    for option in options {
+        match option {
+            TraceOption::AddSpecialTokens(add_special_tokens) => {
+                opts.add_special_tokens = add_special_tokens
+            }
+        }
+    }
+
+    let tokenizer = &tokenizer.resource.0;
+    let (sequence, pair) = match term_to_encode_input(&input)? {
+        EncodeInput::Single(sequence) => (sequence, None),
+        EncodeInput::Dual(sequence, pair) => (sequence, Some(pair)),
+    };
+    let mut sequences = Vec::with_capacity(2);
+    let mut encodings = Vec::with_capacity(2);
+    for (type_id, sequence) in std::iter::once(sequence).chain(pair).enumerate() {
+        let InputSequence::Raw(sequence) = sequence else {
+            unreachable!("only raw sequences are decoded")
+        };
+        let (trace, encoding) = trace_sequence(tokenizer, &sequence, type_id as u32)?;
+        sequences.push(trace);
+        encodings.push(encoding);
+    }
+    let pair = if encodings.len() == 2 {
+        encodings.pop()
+    } else {
+        None
+    };
+    let encoding = encodings.pop().unwrap();
+    let encoding = tokenizer.post_process(encoding, pair, opts.add_special_tokens)?;
+
+    // Tokens that do not belong to any sequence come from the
+    // post-processor, or from padding
+    let post_processor = encoding
+        .get_sequence_ids()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, sequence_id)| sequence_id.is_none())
+        .map(|(index, _)| TraceInsertion {
+            index,
+            token: encoding.get_tokens()[index].clone(),
+            id: encoding.get_ids()[index],
+        })
+        .collect();
+
+    Ok(Trace {
+        sequences,
+        post_processor,
+        encoding: encoding.into(),
+    })
+}
+
 // /////////////////////////////////////////////////////////////////////////////
 // / Training
 // /////////////////////////////////////////////////////////////////////////////
@@ -680,9 +2545,709 @@ pub fn tokenizer_train_from_files(
                 Some(s) => s.clone(),
                 None => "Unknown Panic".to_string(),
             };
-            Err(ExTokenizersError::Internal(panic_message))
+            Err(ExTokenizersError::TrainingPanic(panic_message))
         }
     }?;
 
     Ok(new_tokenizer.into())
 }
+
+// /////////////////////////////////////////////////////////////////////////////
+// / Comparison
+// /////////////////////////////////////////////////////////////////////////////
+
+#[derive(NifTaggedEnum)]
+pub enum DiffOption {
+    Corpus(Vec<String>),
+    MaxSamples(usize),
+    AddSpecialTokens(bool),
+}
+
+#[derive(rustler::NifMap)]
+pub struct ConfigDifference {
+    path: String,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct VocabDiff {
+    added: Vec<(String, u32)>,
+    removed: Vec<(String, u32)>,
+    changed: Vec<(String, u32, u32)>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct MergesDiff {
+    added: Vec<(String, String)>,
+    removed: Vec<(String, String)>,
+    reordered: bool,
+}
+
+#[derive(rustler::NifUnitEnum)]
+pub enum AddedTokenFlag {
+    Special,
+    SingleWord,
+    Lstrip,
+    Rstrip,
+    Normalized,
+}
+
+#[derive(rustler::NifMap)]
+pub struct AddedTokenChange {
+    content: String,
+    flag: AddedTokenFlag,
+    left: bool,
+    right: bool,
+}
+
+#[derive(rustler::NifMap)]
+pub struct AddedTokensDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<AddedTokenChange>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct SampleDiff {
+    index: usize,
+    input: String,
+    left: Vec<u32>,
+    right: Vec<u32>,
+}
+
+#[derive(rustler::NifMap)]
+pub struct TokenizerDiff {
+    equal: bool,
+    config: Vec<ConfigDifference>,
+    vocab: VocabDiff,
+    merges: MergesDiff,
+    scores: Vec<(String, f64, f64)>,
+    added_tokens: AddedTokensDiff,
+    samples: Vec<SampleDiff>,
+}
+
+// Collects the paths where two serialized components differ. Objects are
+// compared key by key and arrays of the same length item by item, other
+// values are compared as a whole
+fn config_differences(
+    path: String,
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+    differences: &mut Vec<ConfigDifference>,
+) {
+    use serde_json::Value;
+
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => {
+            let keys: std::collections::BTreeSet<&String> =
+                left.keys().chain(right.keys()).collect();
+            for key in keys {
+                config_differences(
+                    format!("{path}.{key}"),
+                    left.get(key).unwrap_or(&Value::Null),
+                    right.get(key).unwrap_or(&Value::Null),
+                    differences,
+                );
+            }
+        }
+        (Value::Array(left), Value::Array(right)) if left.len() == right.len() => {
+            for (index, (left, right)) in left.iter().zip(right).enumerate() {
+                config_differences(format!("{path}[{index}]"), left, right, differences);
+            }
+        }
+        _ if left != right => {
+            let encode = |value: &Value| (!value.is_null()).then(|| value.to_string());
+            differences.push(ConfigDifference {
+                path,
+                left: encode(left),
+                right: encode(right),
+            });
+        }
+        _ => {}
+    }
+}
+
+fn vocab_diff(left: &ExTokenizerImpl, right: &ExTokenizerImpl) -> VocabDiff {
+    let left = left.get_vocab(true);
+    let right = right.get_vocab(true);
+    let only_in = |vocab: &HashMap<String, u32>, other: &HashMap<String, u32>| {
+        let mut tokens: Vec<(String, u32)> = vocab
+            .iter()
+            .filter(|(token, _)| !other.contains_key(*token))
+            .map(|(token, id)| (token.clone(), *id))
+            .collect();
+        tokens.sort_unstable_by_key(|(_, id)| *id);
+        tokens
+    };
+    let mut changed: Vec<(String, u32, u32)> = left
+        .iter()
+        .filter_map(|(token, id)| {
+            let other_id = *right.get(token)?;
+            (other_id != *id).then(|| (token.clone(), *id, other_id))
+        })
+        .collect();
+    changed.sort_unstable_by_key(|(_, id, _)| *id);
+
+    VocabDiff {
+        added: only_in(&right, &left),
+        removed: only_in(&left, &right),
+        changed,
+    }
+}
+
+fn merges_diff(left: &serde_json::Value, right: &serde_json::Value) -> MergesDiff {
+    let merges = |model: &serde_json::Value| -> Vec<(String, String)> {
+        serde_json::from_value(model["merges"].clone()).unwrap_or_default()
+    };
+    let (left, right) = (merges(left), merges(right));
+    let left_set: HashSet<&(String, String)> = left.iter().collect();
+    let right_set: HashSet<&(String, String)> = right.iter().collect();
+    // The merges of both sides, in the order of each side
+    let common = |merges: &Vec<(String, String)>, other: &HashSet<&(String, String)>| {
+        merges
+            .iter()
+            .filter(|merge| other.contains(merge))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    MergesDiff {
+        added: right
+            .iter()
+            .filter(|merge| !left_set.contains(merge))
+            .cloned()
+            .collect(),
+        removed: left
+            .iter()
+            .filter(|merge| !right_set.contains(merge))
+            .cloned()
+            .collect(),
+        reordered: common(&left, &right_set) != common(&right, &left_set),
+    }
+}
+
+fn scores_diff(left: &serde_json::Value, right: &serde_json::Value) -> Vec<(String, f64, f64)> {
+    let scores = |model: &serde_json::Value| -> Vec<(String, f64)> {
+        serde_json::from_value(model["vocab"].clone()).unwrap_or_default()
+    };
+    if left["type"] != "Unigram" || right["type"] != "Unigram" {
+        return Vec::new();
+    }
+    let right: HashMap<String, f64> = scores(right).into_iter().collect();
+    scores(left)
+        .into_iter()
+        .filter_map(|(piece, score)| {
+            let other = *right.get(&piece)?;
+            (other != score).then_some((piece, score, other))
+        })
+        .collect()
+}
+
+fn added_tokens_diff(left: &ExTokenizerImpl, right: &ExTokenizerImpl) -> AddedTokensDiff {
+    // The added tokens sorted by id, and indexed by content
+    let added_tokens = |tokenizer: &ExTokenizerImpl| {
+        let mut tokens: Vec<(u32, AddedToken)> =
+            tokenizer.get_added_tokens_decoder().into_iter().collect();
+        tokens.sort_unstable_by_key(|(id, _)| *id);
+        let tokens: Vec<AddedToken> = tokens.into_iter().map(|(_, token)| token).collect();
+        let by_content: HashMap<String, AddedToken> = tokens
+            .iter()
+            .map(|token| (token.content.clone(), token.clone()))
+            .collect();
+        (tokens, by_content)
+    };
+    let (left, left_by_content) = added_tokens(left);
+    let (right, right_by_content) = added_tokens(right);
+
+    let mut changed = Vec::new();
+    for token in &left {
+        let Some(other) = right_by_content.get(&token.content) else {
+            continue;
+        };
+        let flags = [
+            (AddedTokenFlag::Special, token.special, other.special),
+            (
+                AddedTokenFlag::SingleWord,
+                token.single_word,
+                other.single_word,
+            ),
+            (AddedTokenFlag::Lstrip, token.lstrip, other.lstrip),
+            (AddedTokenFlag::Rstrip, token.rstrip, other.rstrip),
+            (
+                AddedTokenFlag::Normalized,
+                token.normalized,
+                other.normalized,
+            ),
+        ];
+        for (flag, left, right) in flags {
+            if left != right {
+                changed.push(AddedTokenChange {
+                    content: token.content.clone(),
+                    flag,
+                    left,
+                    right,
+                });
+            }
+        }
+    }
+
+    let only_in = |tokens: &[AddedToken], other: &HashMap<String, AddedToken>| {
+        tokens
+            .iter()
+            .filter(|token| !other.contains_key(&token.content))
+            .map(|token| token.content.clone())
+            .collect()
+    };
+
+    AddedTokensDiff {
+        added: only_in(&right, &left_by_content),
+        removed: only_in(&left, &right_by_content),
+        changed,
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_diff(
+    left: ExTokenizersTokenizer,
+    right: ExTokenizersTokenizer,
+    options: Vec<DiffOption>,
+) -> Result<TokenizerDiff, ExTokenizersError> {
+    struct Opts {
+        corpus: Vec<String>,
+        max_samples: usize,
+        add_special_tokens: bool,
+    }
+
+    // Default values
+    let mut opts = Opts {
+        corpus: Vec::new(),
+        max_samples: 10,
+        add_special_tokens: true,
+    };
+
+    options.into_iter().for_each(|option| match option {
+        DiffOption::Corpus(corpus) => opts.corpus = corpus,
+        DiffOption::MaxSamples(max_samples) => opts.max_samples = max_samples,
+        DiffOption::AddSpecialTokens(add_special_tokens) => {
+            opts.add_special_tokens = add_special_tokens
+        }
+    });
+
+    let left = &left.resource.0;
+    let right = &right.resource.0;
+    let to_json = |tokenizer: &ExTokenizerImpl| {
+        serde_json::to_value(tokenizer)
+            .map_err(|error| ExTokenizersError::Internal(error.to_string()))
+    };
+    let (left_json, right_json) = (to_json(left)?, to_json(right)?);
+
+    let mut config = Vec::new();
+    for component in [
+        "normalizer",
+        "pre_tokenizer",
+        "model",
+        "post_processor",
+        "decoder",
+        "padding",
+        "truncation",
+    ] {
+        let options = |json: &serde_json::Value| {
+            let mut value = json[component].clone();
+            if let Some(object) = value.as_object_mut().filter(|_| component == "model") {
+                object.remove("vocab");
+                object.remove("merges");
+            }
+            value
+        };
+        config_differences(
+            component.to_string(),
+            &options(&left_json),
+            &options(&right_json),
+            &mut config,
+        );
+    }
+
+    let mut samples = Vec::new();
+    if !opts.corpus.is_empty() && opts.max_samples > 0 {
+        let encode = |tokenizer: &ExTokenizerImpl| -> Result<Vec<Encoding>, ExTokenizersError> {
+            let mut tokenizer = tokenizer.clone();
+            tokenizer.with_padding(None);
+            Ok(tokenizer.encode_batch(opts.corpus.clone(), opts.add_special_tokens)?)
+        };
+        let (left_encodings, right_encodings) = (encode(left)?, encode(right)?);
+        samples = left_encodings
+            .iter()
+            .zip(&right_encodings)
+            .enumerate()
+            .filter(|(_, (left, right))| left.get_ids() != right.get_ids())
+            .take(opts.max_samples)
+            .map(|(index, (left, right))| SampleDiff {
+                index,
+                input: opts.corpus[index].clone(),
+                left: left.get_ids().to_vec(),
+                right: right.get_ids().to_vec(),
+            })
+            .collect();
+    }
+
+    let vocab = vocab_diff(left, right);
+    let merges = merges_diff(&left_json["model"], &right_json["model"]);
+    let scores = scores_diff(&left_json["model"], &right_json["model"]);
+    let added_tokens = added_tokens_diff(left, right);
+    let equal = config.is_empty()
+        && vocab.added.is_empty()
+        && vocab.removed.is_empty()
+        && vocab.changed.is_empty()
+        && merges.added.is_empty()
+        && merges.removed.is_empty()
+        && !merges.reordered
+        && scores.is_empty()
+        && added_tokens.added.is_empty()
+        && added_tokens.removed.is_empty()
+        && added_tokens.changed.is_empty()
+        && samples.is_empty();
+
+    Ok(TokenizerDiff {
+        equal,
+        config,
+        vocab,
+        merges,
+        scores,
+        added_tokens,
+        samples,
+    })
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// / Validation
+// /////////////////////////////////////////////////////////////////////////////
+
+#[derive(rustler::NifUnitEnum)]
+pub enum ValidationSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(rustler::NifUnitEnum)]
+pub enum ValidationRule {
+    UnknownSpecialToken,
+    SpecialTokenIdMismatch,
+    SpecialTokenNotSpecial,
+    UnknownPadToken,
+    PadIdMismatch,
+    UnknownUnkToken,
+    UnkIdOutOfRange,
+    ByteLevelMismatch,
+    MetaspaceMismatch,
+    WordPiecePrefixMismatch,
+    TruncationTooShort,
+}
+
+#[derive(rustler::NifMap)]
+pub struct ValidationIssue {
+    severity: ValidationSeverity,
+    rule: ValidationRule,
+    path: String,
+    message: String,
+}
+
+// Collects the components of a pipeline step, along with their path,
+// flattening sequences
+fn pipeline_components<'a>(
+    path: String,
+    value: &'a serde_json::Value,
+    components: &mut Vec<(String, &'a serde_json::Value)>,
+) {
+    if value["type"] != "Sequence" {
+        if value.is_object() {
+            components.push((path, value));
+        }
+        return;
+    }
+    if let Some(object) = value.as_object() {
+        for (key, value) in object {
+            if let Some(values) = value.as_array() {
+                for (index, value) in values.iter().enumerate() {
+                    pipeline_components(format!("{path}.{key}[{index}]"), value, components);
+                }
+            }
+        }
+    }
+}
+
+fn find_component<'a>(
+    components: &[(String, &'a serde_json::Value)],
+    component_type: &str,
+) -> Option<(String, &'a serde_json::Value)> {
+    components
+        .iter()
+        .find(|(_, value)| value["type"] == component_type)
+        .map(|(path, value)| (path.clone(), *value))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_validate(
+    tokenizer: ExTokenizersTokenizer,
+) -> Result<Vec<ValidationIssue>, ExTokenizersError> {
+    use ValidationRule::*;
+    use ValidationSeverity::*;
+
+    let tokenizer = &tokenizer.resource.0;
+    let mut json = serde_json::to_value(tokenizer)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    let mut issues = Vec::new();
+    let mut issue = |severity, rule, path: &str, message: String| {
+        issues.push(ValidationIssue {
+            severity,
+            rule,
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    // Special tokens of the post-processor
+    let added_tokens = tokenizer.get_added_tokens_decoder();
+    special_token_ids(
+        &mut json["post_processor"],
+        "post_processor",
+        &mut |path, token, id| match tokenizer.token_to_id(token) {
+            None => issue(
+                Error,
+                UnknownSpecialToken,
+                path,
+                format!("special token {token:?} is not in the vocabulary"),
+            ),
+            Some(vocab_id) if id.as_u64() != Some(vocab_id as u64) => issue(
+                Error,
+                SpecialTokenIdMismatch,
+                path,
+                format!("special token {token:?} has id {id}, but {vocab_id} in the vocabulary"),
+            ),
+            Some(vocab_id) => {
+                if !added_tokens
+                    .get(&vocab_id)
+                    .is_some_and(|added_token| added_token.special)
+                {
+                    issue(
+                        Warning,
+                        SpecialTokenNotSpecial,
+                        path,
+                        format!("special token {token:?} is not a special added token"),
+                    )
+                }
+            }
+        },
+    );
+
+    // Padding
+    if let Some(params) = tokenizer.get_padding() {
+        match tokenizer.token_to_id(&params.pad_token) {
+            None => issue(
+                Error,
+                UnknownPadToken,
+                "padding.pad_token",
+                format!(
+                    "padding token {:?} is not in the vocabulary",
+                    params.pad_token
+                ),
+            ),
+            Some(vocab_id) if vocab_id != params.pad_id => issue(
+                Error,
+                PadIdMismatch,
+                "padding.pad_id",
+                format!(
+                    "padding token {:?} has id {}, but {} in the vocabulary",
+                    params.pad_token, params.pad_id, vocab_id
+                ),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    // Unknown token of the model
+    let model = &json["model"];
+    if let Some(unk_token) = model["unk_token"].as_str() {
+        if tokenizer.get_model().token_to_id(unk_token).is_none() {
+            issue(
+                Error,
+                UnknownUnkToken,
+                "model.unk_token",
+                format!("unknown token {unk_token:?} is not in the model vocabulary"),
+            )
+        }
+    }
+    if let Some(unk_id) = model["unk_id"].as_u64() {
+        let vocab_size = tokenizer.get_model().get_vocab_size();
+        if unk_id >= vocab_size as u64 {
+            issue(
+                Error,
+                UnkIdOutOfRange,
+                "model.unk_id",
+                format!("unknown token id {unk_id} is out of the vocabulary of size {vocab_size}"),
+            )
+        }
+    }
+
+    // Pre-tokenization and decoding
+    let mut encoding_components = Vec::new();
+    pipeline_components(
+        "normalizer".to_string(),
+        &json["normalizer"],
+        &mut encoding_components,
+    );
+    pipeline_components(
+        "pre_tokenizer".to_string(),
+        &json["pre_tokenizer"],
+        &mut encoding_components,
+    );
+    let mut decoders = Vec::new();
+    pipeline_components("decoder".to_string(), &json["decoder"], &mut decoders);
+
+    match (
+        find_component(&encoding_components, "ByteLevel"),
+        find_component(&decoders, "ByteLevel"),
+    ) {
+        (Some((path, _)), None) => issue(
+            Warning,
+            ByteLevelMismatch,
+            "decoder",
+            format!("{path} is byte-level, but there is no byte-level decoder"),
+        ),
+        (None, Some((path, _))) => issue(
+            Warning,
+            ByteLevelMismatch,
+            &path,
+            "the decoder is byte-level, but the input is not byte-level encoded".to_string(),
+        ),
+        _ => {}
+    }
+
+    match (
+        find_component(&encoding_components, "Metaspace"),
+        find_component(&decoders, "Metaspace"),
+    ) {
+        (Some((path, _)), None) => issue(
+            Warning,
+            MetaspaceMismatch,
+            "decoder",
+            format!("{path} is a metaspace pre-tokenizer, but there is no metaspace decoder"),
+        ),
+        (Some((_, pre_tokenizer)), Some((path, decoder))) => {
+            for key in ["replacement", "prepend_scheme"] {
+                if pre_tokenizer[key] != decoder[key] {
+                    issue(
+                        Warning,
+                        MetaspaceMismatch,
+                        &format!("{path}.{key}"),
+                        format!(
+                            "the metaspace decoder has {key} {}, but the pre-tokenizer {}",
+                            decoder[key], pre_tokenizer[key]
+                        ),
+                    )
+                }
+            }
+        }
+        _ => {}
+    }
+
+    if let (Some(prefix), Some((path, decoder))) = (
+        model["continuing_subword_prefix"]
+            .as_str()
+            .filter(|_| model["type"] == "WordPiece"),
+        find_component(&decoders, "WordPiece"),
+    ) {
+        if decoder["prefix"] != prefix {
+            issue(
+                Warning,
+                WordPiecePrefixMismatch,
+                &format!("{path}.prefix"),
+                format!(
+                    "the decoder prefix is {}, but the model prefix {prefix:?}",
+                    decoder["prefix"]
+                ),
+            )
+        }
+    }
+
+    // Truncation
+    if let Some(params) = tokenizer.get_truncation() {
+        let added_tokens = tokenizer
+            .get_post_processor()
+            .map_or(0, |post_processor| post_processor.added_tokens(false));
+        if params.max_length < added_tokens {
+            issue(
+                Error,
+                TruncationTooShort,
+                "truncation.max_length",
+                format!(
+                    "maximum length {} is shorter than the {added_tokens} special tokens added",
+                    params.max_length
+                ),
+            )
+        }
+    }
+
+    Ok(issues)
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// / Fingerprint
+// /////////////////////////////////////////////////////////////////////////////
+
+#[derive(rustler::NifUnitEnum)]
+pub enum FingerprintComponent {
+    Normalizer,
+    PreTokenizer,
+    Model,
+    PostProcessor,
+    Decoder,
+    AddedTokens,
+    Padding,
+    Truncation,
+}
+
+#[derive(NifTaggedEnum)]
+pub enum FingerprintOption {
+    Exclude(Vec<FingerprintComponent>),
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tokenizer_fingerprint(
+    tokenizer: ExTokenizersTokenizer,
+    options: Vec<FingerprintOption>,
+) -> Result<String, ExTokenizersError> {
+    struct Opts {
+        exclude: Vec<FingerprintComponent>,
+    }
+
+    // Default values
+    let mut opts = Opts {
+        exclude: Vec::new(),
+    };
+
+    options.into_iter().for_each(|option| match option {
+        FingerprintOption::Exclude(exclude) => opts.exclude = exclude,
+    });
+
+    let mut json = serde_json::to_value(&tokenizer.resource.0)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    if let Some(object) = json.as_object_mut() {
+        // The version of the serialization format does not change behaviour
+        object.remove("version");
+        for component in opts.exclude {
+            object.remove(match component {
+                FingerprintComponent::Normalizer => "normalizer",
+                FingerprintComponent::PreTokenizer => "pre_tokenizer",
+                FingerprintComponent::Model => "model",
+                FingerprintComponent::PostProcessor => "post_processor",
+                FingerprintComponent::Decoder => "decoder",
+                FingerprintComponent::AddedTokens => "added_tokens",
+                FingerprintComponent::Padding => "padding",
+                FingerprintComponent::Truncation => "truncation",
+            });
+        }
+    }
+
+    Ok(json_fingerprint(&json))
+}
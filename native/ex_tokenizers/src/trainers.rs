@@ -36,14 +36,12 @@ impl tokenizers::Trainer for ExTokenizersTrainer {
     }
 
     fn train(&self, model: &mut Self::Model) -> tokenizers::Result<Vec<tokenizers::AddedToken>> {
-        let special_tokens = self
-            .resource
-            .0
-            .read()
-            .unwrap()
-            .train(&mut model.resource.0.write().unwrap())?;
-
-        Ok(special_tokens)
+        let mut wrapper = model.resource.0.write().unwrap();
+        let result = self.resource.0.read().unwrap().train(&mut wrapper);
+        // Lookup tables built before training no longer match the model
+        model.resource.clear_index();
+
+        result
     }
 
     fn feed<I, S, F>(&mut self, iterator: I, process: F) -> tokenizers::Result<()>
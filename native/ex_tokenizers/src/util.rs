@@ -1,7 +1,10 @@
 use std::panic::RefUnwindSafe;
 
-use rustler::Encoder;
-use tokenizers::{PaddingDirection, TruncationDirection};
+use rustler::{Binary, Encoder, Env, OwnedBinary};
+use tokenizers::normalizer::Range;
+use tokenizers::{NormalizedString, PaddingDirection, TruncationDirection};
+
+use crate::error::ExTokenizersError;
 
 #[macro_export]
 macro_rules! new_info {
@@ -28,6 +31,12 @@ impl rustler::Encoder for Info {
     }
 }
 
+#[derive(rustler::NifUnitEnum, Clone, Copy, PartialEq)]
+pub enum OffsetUnit {
+    Byte,
+    Char,
+}
+
 #[derive(rustler::NifUnitEnum, Clone)]
 pub enum Direction {
     Left,
@@ -69,3 +78,172 @@ impl From<&Direction> for TruncationDirection {
         }
     }
 }
+
+// Writes values to a binary, each as its native-endian bytes
+fn ne_binary<'a, const N: usize>(
+    env: Env<'a>,
+    values: impl ExactSizeIterator<Item = [u8; N]>,
+) -> Binary<'a> {
+    let mut binary = OwnedBinary::new(values.len() * N).unwrap();
+    binary
+        .as_mut_slice()
+        .chunks_exact_mut(N)
+        .zip(values)
+        .for_each(|(bytes, value)| bytes.copy_from_slice(&value));
+    binary.release(env)
+}
+
+pub fn u32_binary<'a>(env: Env<'a>, values: &[u32]) -> Binary<'a> {
+    ne_binary(env, values.iter().map(|value| value.to_ne_bytes()))
+}
+
+pub fn i32_binary<'a>(env: Env<'a>, values: &[i32]) -> Binary<'a> {
+    ne_binary(env, values.iter().map(|value| value.to_ne_bytes()))
+}
+
+// Maps every byte of the normalized string to the range of bytes it comes
+// from in the original string
+pub fn normalized_alignments(normalized: &NormalizedString) -> Vec<(usize, usize)> {
+    let shift = normalized.offsets_original().0;
+    (0..normalized.len())
+        .map(|index| {
+            normalized
+                .convert_offsets(Range::Normalized(index..index + 1))
+                .map_or((shift, shift), |range| {
+                    (range.start + shift, range.end + shift)
+                })
+        })
+        .collect()
+}
+
+// Same as `normalized_alignments`, but with one range of original char
+// offsets for every char of the normalized string. Only meant for strings
+// that are not a slice of a bigger one
+pub fn normalized_char_alignments(normalized: &NormalizedString) -> Vec<(usize, usize)> {
+    let char_starts: Vec<usize> = normalized
+        .get_original()
+        .char_indices()
+        .map(|(start, _)| start)
+        .collect();
+    let char_index = |byte: usize| char_starts.partition_point(|start| *start < byte);
+    normalized
+        .get()
+        .char_indices()
+        .map(|(start, char)| {
+            normalized
+                .convert_offsets(Range::Normalized(start..start + char.len_utf8()))
+                .map_or((0, 0), |range| {
+                    (char_index(range.start), char_index(range.end))
+                })
+        })
+        .collect()
+}
+
+// A SplitMix64 generator, so that seeded results stay the same across
+// platforms and dependency versions
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    // A generator for one row of a batch, independent from the other rows
+    pub fn for_row(seed: u64, row: usize) -> Self {
+        let mut rng = Self::new(seed ^ Self::new(row as u64).next_u64());
+        Self::new(rng.next_u64())
+    }
+
+    // A seed that differs on every call, for when none is given
+    pub fn random_seed() -> u64 {
+        use std::hash::{BuildHasher, Hasher};
+        std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // A float uniformly distributed in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// Writes a JSON value with object keys sorted and no whitespace, so that
+// equal values always give the same text
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries: Vec<_> = object.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::from(key.as_str()).to_string());
+                out.push(':');
+                write_canonical_json(value, out);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(values) => {
+            out.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical_json(value, out);
+            }
+            out.push(']');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+// A 128-bit FNV-1a hash of the canonical JSON of a value, as hexadecimal.
+// The value comes from the serialization of the tokenizers crate, so
+// fingerprints change whenever that serialization does
+pub fn json_fingerprint(value: &serde_json::Value) -> String {
+    let mut json = String::new();
+    write_canonical_json(value, &mut json);
+    let hash = json
+        .bytes()
+        .fold(0x6c62272e07bb014262b821756295c58d, |hash, byte| {
+            (hash ^ byte as u128).wrapping_mul(0x0000000001000000000000000000013b)
+        });
+    format!("{hash:032x}")
+}
+
+pub fn fingerprint<T: serde::Serialize>(value: &T) -> Result<String, ExTokenizersError> {
+    let value = serde_json::to_value(value)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    Ok(json_fingerprint(&value))
+}
+
+// Deserializes a JSON text, locating the offending field on failure
+pub fn from_json_str<'a, T>(data: &'a str) -> Result<T, ExTokenizersError>
+where
+    T: serde::Deserialize<'a>,
+{
+    let mut deserializer = serde_json::Deserializer::from_str(data);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+        // The root of the document has no path
+        let path = match error.path().to_string() {
+            path if path == "." => String::new(),
+            path => path,
+        };
+        ExTokenizersError::json_parse(error.into_inner(), path)
+    })?;
+    deserializer
+        .end()
+        .map_err(|error| ExTokenizersError::json_parse(error, String::new()))?;
+    Ok(value)
+}
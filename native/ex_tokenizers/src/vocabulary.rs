@@ -0,0 +1,879 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use rustler::{Binary, Env, NifTaggedEnum};
+use serde_json::Value;
+use tokenizers::pre_tokenizers::byte_level::ByteLevel;
+use tokenizers::{AddedToken, Encoding};
+
+use crate::added_token::AddedTokenOption;
+use crate::error::ExTokenizersError;
+use crate::post_processors::ExTokenizersPostProcessor;
+use crate::tokenizer::{ExTokenizerImpl, ExTokenizersTokenizer};
+use crate::util::{i32_binary, u32_binary};
+
+// Calls `fun` with every special token of a serialized post-processor and
+// the value holding its id, along with the path of that value under
+// `path`, for the template, BERT and RoBERTa processors and any sequence
+// of them
+pub(crate) fn special_token_ids<F>(value: &mut Value, path: &str, fun: &mut F)
+where
+    F: FnMut(&str, &mut String, &mut Value),
+{
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(mut tokens)) = object.remove("tokens") {
+                if let Some(Value::Array(ids)) = object.get_mut("ids") {
+                    tokens.iter_mut().zip(ids.iter_mut()).enumerate().for_each(
+                        |(index, (token, id))| {
+                            if let Value::String(token) = token {
+                                fun(&format!("{path}.ids[{index}]"), token, id)
+                            }
+                        },
+                    );
+                }
+                object.insert(String::from("tokens"), Value::Array(tokens));
+            }
+            for key in ["sep", "cls"] {
+                if let Some(Value::Array(pair)) = object.get_mut(key) {
+                    if let [Value::String(token), id] = pair.as_mut_slice() {
+                        fun(&format!("{path}.{key}"), token, id)
+                    }
+                }
+            }
+            object
+                .iter_mut()
+                .for_each(|(key, value)| special_token_ids(value, &format!("{path}.{key}"), fun));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, value)| special_token_ids(value, &format!("{path}[{index}]"), fun)),
+        _ => {}
+    }
+}
+
+// Builds a tokenizer from a serialized one, whose model vocabulary was
+// changed. Added tokens get their ids from the new model, and the ids of
+// special tokens in the post-processor and padding are updated to match
+fn rebuild_tokenizer(mut json: Value) -> Result<ExTokenizerImpl, ExTokenizersError> {
+    let mut post_processor = json["post_processor"].take();
+    let mut tokenizer: ExTokenizerImpl = serde_json::from_value(json)
+        .map_err(|error| ExTokenizersError::Other(error.to_string()))?;
+
+    if !post_processor.is_null() {
+        let mut missing = None;
+        special_token_ids(
+            &mut post_processor,
+            "post_processor",
+            &mut |_, token, id| match tokenizer.token_to_id(token) {
+                Some(new_id) => *id = Value::from(new_id),
+                None => missing = Some(token.to_owned()),
+            },
+        );
+        if let Some(token) = missing {
+            return Err(ExTokenizersError::UnknownToken(format!(
+                "special token {token:?} of the post-processor is not in the vocabulary"
+            )));
+        }
+        let post_processor: ExTokenizersPostProcessor = serde_json::from_value(post_processor)
+            .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+        tokenizer.with_post_processor(Some(post_processor));
+    }
+
+    if let Some(pad_token) = tokenizer
+        .get_padding()
+        .map(|padding| padding.pad_token.clone())
+    {
+        let pad_id = tokenizer.token_to_id(&pad_token).ok_or_else(|| {
+            ExTokenizersError::UnknownToken(format!(
+                "pad token {pad_token:?} of the padding is not in the vocabulary"
+            ))
+        })?;
+        if let Some(padding) = tokenizer.get_padding_mut() {
+            padding.pad_id = pad_id;
+        }
+    }
+
+    Ok(tokenizer)
+}
+
+// Encodes every text of a corpus, without truncation and padding
+fn encode_corpus(
+    tokenizer: &ExTokenizerImpl,
+    corpus: Vec<String>,
+    add_special_tokens: bool,
+) -> Result<Vec<Encoding>, ExTokenizersError> {
+    let mut encoder = tokenizer.clone();
+    encoder.with_truncation(None)?;
+    encoder.with_padding(None);
+    Ok(encoder.encode_batch(corpus, add_special_tokens)?)
+}
+
+// The id following the highest id of the vocabulary, added tokens included
+fn vocab_end(tokenizer: &ExTokenizerImpl) -> u32 {
+    tokenizer
+        .get_vocab(true)
+        .values()
+        .max()
+        .map_or(0, |id| id + 1)
+}
+
+// The tokens of a vocabulary map, sorted by id
+fn vocab_by_id(vocab: &Value) -> Vec<(String, u64)> {
+    let mut tokens: Vec<(String, u64)> = vocab
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(token, id)| Some((token.clone(), id.as_u64()?)))
+        .collect();
+    tokens.sort_unstable_by_key(|(_, id)| *id);
+    tokens
+}
+
+// Keeps the tokens of a vocabulary map in `keep`, with new ids following
+// the order of the old ones
+fn retain_vocab(vocab: &mut Value, keep: &HashSet<String>) {
+    *vocab = Value::Object(
+        vocab_by_id(vocab)
+            .into_iter()
+            .filter(|(token, _)| keep.contains(token))
+            .enumerate()
+            .map(|(id, (token, _))| (token, Value::from(id)))
+            .collect(),
+    );
+}
+
+// Removes every token of a serialized model that is not in `keep`. For
+// BPE, the tokens merged into a kept token are kept as well, along with
+// the merges between kept tokens, so that words tokenize the same way
+fn prune_model(model: &mut Value, keep: &mut HashSet<String>) -> Result<(), ExTokenizersError> {
+    match model["type"].as_str() {
+        Some("BPE") => {
+            let prefix_length = model["continuing_subword_prefix"]
+                .as_str()
+                .map_or(0, str::len);
+            let merges: Vec<(String, String)> = serde_json::from_value(model["merges"].take())
+                .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+            let merged: Vec<String> = merges
+                .iter()
+                .map(|(left, right)| format!("{}{}", left, &right[prefix_length..]))
+                .collect();
+
+            let mut producers: HashMap<&str, Vec<usize>> = HashMap::new();
+            merged
+                .iter()
+                .enumerate()
+                .for_each(|(index, token)| producers.entry(token).or_default().push(index));
+            let mut pending: Vec<String> = keep.iter().cloned().collect();
+            while let Some(token) = pending.pop() {
+                for index in producers.get(token.as_str()).into_iter().flatten() {
+                    let (left, right) = &merges[*index];
+                    for part in [left, right] {
+                        if keep.insert(part.clone()) {
+                            pending.push(part.clone());
+                        }
+                    }
+                }
+            }
+
+            let merges: Vec<(String, String)> = merges
+                .into_iter()
+                .zip(&merged)
+                .filter(|((left, right), token)| {
+                    keep.contains(left) && keep.contains(right) && keep.contains(*token)
+                })
+                .map(|(merge, _)| merge)
+                .collect();
+            model["merges"] = serde_json::to_value(merges)
+                .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+            retain_vocab(&mut model["vocab"], keep);
+        }
+        Some("WordPiece") | Some("WordLevel") => retain_vocab(&mut model["vocab"], keep),
+        Some("Unigram") => {
+            let unk_piece = model["unk_id"]
+                .as_u64()
+                .and_then(|unk_id| model["vocab"][unk_id as usize][0].as_str())
+                .map(str::to_owned);
+            let vocab: Vec<Value> = model["vocab"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|entry| entry[0].as_str().is_some_and(|piece| keep.contains(piece)))
+                .cloned()
+                .collect();
+            model["unk_id"] = match unk_piece {
+                Some(unk_piece) => vocab
+                    .iter()
+                    .position(|entry| entry[0].as_str() == Some(unk_piece.as_str()))
+                    .into(),
+                None => Value::Null,
+            };
+            model["vocab"] = Value::Array(vocab);
+        }
+        _ => {
+            return Err(ExTokenizersError::Other(String::from(
+                "vocabulary pruning requires a BPE, WordPiece, WordLevel or Unigram model",
+            )))
+        }
+    }
+    Ok(())
+}
+
+// The tokens a model needs regardless of the corpus: its unknown token,
+// and the tokens of every char of the alphabet, with the subword prefix
+// and suffix it may take
+fn model_tokens(model: &Value, alphabet: &[char]) -> Vec<String> {
+    let unk_token = match model["unk_id"].as_u64() {
+        Some(unk_id) => model["vocab"][unk_id as usize][0].as_str(),
+        None => model["unk_token"].as_str(),
+    };
+    let prefix = model["continuing_subword_prefix"].as_str().unwrap_or("");
+    let suffix = model["end_of_word_suffix"].as_str().unwrap_or("");
+    unk_token
+        .map(str::to_owned)
+        .into_iter()
+        .chain(alphabet.iter().flat_map(|char| {
+            [
+                format!("{char}"),
+                format!("{prefix}{char}"),
+                format!("{char}{suffix}"),
+                format!("{prefix}{char}{suffix}"),
+            ]
+        }))
+        .collect()
+}
+
+// Whether a serialized component is or contains a ByteLevel one
+fn is_byte_level(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            object.get("type").and_then(Value::as_str) == Some("ByteLevel")
+                || object.values().any(is_byte_level)
+        }
+        Value::Array(values) => values.iter().any(is_byte_level),
+        _ => false,
+    }
+}
+
+#[derive(NifTaggedEnum)]
+pub enum PruneOption {
+    Keep(Vec<String>),
+    Alphabet(Vec<u32>),
+    KeepBytes(bool),
+}
+
+#[derive(rustler::NifMap)]
+pub struct PrunedVocabulary<'a> {
+    tokenizer: ExTokenizersTokenizer,
+    new_ids: Binary<'a>,
+    old_ids: Binary<'a>,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vocabulary_prune<'a>(
+    env: Env<'a>,
+    tokenizer: ExTokenizersTokenizer,
+    corpus: Vec<String>,
+    options: Vec<PruneOption>,
+) -> Result<PrunedVocabulary<'a>, ExTokenizersError> {
+    struct Opts {
+        keep: Vec<String>,
+        alphabet: Vec<char>,
+        keep_bytes: bool,
+    }
+
+    // Default values
+    let mut opts = Opts {
+        keep: Vec::new(),
+        alphabet: Vec::new(),
+        keep_bytes: true,
+    };
+
+    for option in options {
+        match option {
+            PruneOption::Keep(tokens) => opts.keep = tokens,
+            PruneOption::Alphabet(alphabet) => {
+                opts.alphabet = alphabet
+                    .into_iter()
+                    .map(|char| char::from_u32(char).ok_or(ExTokenizersError::InvalidChar))
+                    .collect::<Result<_, _>>()?
+            }
+            PruneOption::KeepBytes(keep_bytes) => opts.keep_bytes = keep_bytes,
+        }
+    }
+
+    let old = &tokenizer.resource.0;
+    let mut json = serde_json::to_value(old)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+
+    let encodings = encode_corpus(old, corpus, true)?;
+
+    let mut keep: HashSet<String> = encodings
+        .iter()
+        .flat_map(|encoding| encoding.get_tokens())
+        .cloned()
+        .collect();
+    keep.extend(opts.keep);
+    keep.extend(
+        old.get_added_tokens_decoder()
+            .into_values()
+            .map(|token| token.content),
+    );
+    if opts.keep_bytes {
+        keep.extend((0..=255).map(|byte: u8| format!("<0x{byte:02X}>")));
+        // Byte-level tokenizers map every byte to a char of their alphabet
+        if ["normalizer", "pre_tokenizer", "decoder"]
+            .iter()
+            .any(|component| is_byte_level(&json[component]))
+        {
+            opts.alphabet.extend(ByteLevel::alphabet());
+        }
+    }
+    keep.extend(model_tokens(&json["model"], &opts.alphabet));
+    special_token_ids(
+        &mut json["post_processor"],
+        "post_processor",
+        &mut |_, token, _| {
+            keep.insert(token.to_owned());
+        },
+    );
+    if let Some(padding) = old.get_padding() {
+        keep.insert(padding.pad_token.clone());
+    }
+
+    prune_model(&mut json["model"], &mut keep)?;
+    let new = rebuild_tokenizer(json)?;
+
+    let mut new_ids = vec![-1; vocab_end(old) as usize];
+    let mut old_ids = vec![0; vocab_end(&new) as usize];
+    for (token, old_id) in old.get_vocab(true) {
+        if let Some(new_id) = new.token_to_id(&token) {
+            new_ids[old_id as usize] = new_id as i32;
+            old_ids[new_id as usize] = old_id;
+        }
+    }
+
+    Ok(PrunedVocabulary {
+        tokenizer: new.into(),
+        new_ids: i32_binary(env, &new_ids),
+        old_ids: u32_binary(env, &old_ids),
+    })
+}
+
+// A token learned by merging a pair of adjacent tokens, with the number of
+// times the pair occurred and the number of tokens in the corpus after the
+// merge
+struct LearnedToken {
+    left: String,
+    right: String,
+    token: String,
+    count: u64,
+    total: u64,
+}
+
+// Learns new tokens from the words of a corpus, given as their current
+// tokens with their count, the way BPE training does: the most frequent
+// pair of adjacent tokens is merged into a new token, over and over.
+// Pairs merging into a token of `vocab` are skipped
+fn learn_tokens(
+    words: HashMap<Vec<String>, u64>,
+    vocab: &HashSet<String>,
+    prefix: &str,
+    size: usize,
+    min_frequency: u64,
+    max_token_length: Option<usize>,
+) -> Vec<LearnedToken> {
+    let mut symbols: Vec<String> = Vec::new();
+    let mut symbol_ids: HashMap<String, usize> = HashMap::new();
+    let mut intern = |symbol: String, symbols: &mut Vec<String>| {
+        *symbol_ids.entry(symbol.clone()).or_insert_with(|| {
+            symbols.push(symbol);
+            symbols.len() - 1
+        })
+    };
+
+    let mut words: Vec<(Vec<usize>, u64)> = words
+        .into_iter()
+        .map(|(word, count)| {
+            let word = word
+                .into_iter()
+                .map(|token| intern(token, &mut symbols))
+                .collect();
+            (word, count)
+        })
+        .collect();
+    let mut total: u64 = words
+        .iter()
+        .map(|(word, count)| word.len() as u64 * count)
+        .sum();
+
+    let mut counts: HashMap<(usize, usize), u64> = HashMap::new();
+    let mut locations: HashMap<(usize, usize), HashSet<usize>> = HashMap::new();
+    for (index, (word, count)) in words.iter().enumerate() {
+        for pair in word.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += count;
+            locations
+                .entry((pair[0], pair[1]))
+                .or_default()
+                .insert(index);
+        }
+    }
+
+    // Pairs by count, with ties broken by the tokens, so that results do
+    // not depend on the hashing order. Pairs are pushed again whenever
+    // their count changes, and outdated entries are skipped when popped
+    let entry = |pair: (usize, usize), count: u64, symbols: &[String]| {
+        let tokens = (symbols[pair.0].clone(), symbols[pair.1].clone());
+        (count, Reverse(tokens), pair)
+    };
+    let mut queue: BinaryHeap<_> = counts
+        .iter()
+        .map(|(pair, count)| entry(*pair, *count, &symbols))
+        .collect();
+
+    let mut learned = Vec::new();
+    while learned.len() < size {
+        let Some((count, _, pair)) = queue.pop() else {
+            break;
+        };
+        if counts.get(&pair) != Some(&count) {
+            continue;
+        }
+        if count < min_frequency {
+            break;
+        }
+
+        let (left, right) = (symbols[pair.0].clone(), symbols[pair.1].clone());
+        let token = format!("{}{}", left, right.strip_prefix(prefix).unwrap_or(&right));
+        if vocab.contains(&token)
+            || max_token_length.is_some_and(|length| token.chars().count() > length)
+        {
+            continue;
+        }
+
+        let merged = intern(token.clone(), &mut symbols);
+        let mut changed: HashSet<(usize, usize)> = HashSet::new();
+        for index in locations.remove(&pair).unwrap_or_default() {
+            let (word, word_count) = &mut words[index];
+            for old_pair in word.windows(2) {
+                if let Some(count) = counts.get_mut(&(old_pair[0], old_pair[1])) {
+                    *count -= *word_count;
+                    changed.insert((old_pair[0], old_pair[1]));
+                }
+            }
+
+            let mut new_word = Vec::with_capacity(word.len());
+            let mut position = 0;
+            while position < word.len() {
+                if position + 1 < word.len() && (word[position], word[position + 1]) == pair {
+                    new_word.push(merged);
+                    position += 2;
+                } else {
+                    new_word.push(word[position]);
+                    position += 1;
+                }
+            }
+            total -= (word.len() - new_word.len()) as u64 * *word_count;
+
+            for new_pair in new_word.windows(2) {
+                *counts.entry((new_pair[0], new_pair[1])).or_default() += *word_count;
+                changed.insert((new_pair[0], new_pair[1]));
+                locations
+                    .entry((new_pair[0], new_pair[1]))
+                    .or_default()
+                    .insert(index);
+            }
+            *word = new_word;
+        }
+        counts.retain(|_, count| *count > 0);
+        for pair in changed {
+            if let Some(count) = counts.get(&pair) {
+                queue.push(entry(pair, *count, &symbols));
+            }
+        }
+
+        learned.push(LearnedToken {
+            left,
+            right,
+            token,
+            count,
+            total,
+        });
+    }
+
+    learned
+}
+
+// Counts the words of the encodings, as their tokens. Tokens that cannot
+// be merged, such as added tokens, unknown tokens and byte fallback
+// tokens, split the words they are part of
+fn count_words(encodings: &[Encoding], barriers: &HashSet<u32>) -> HashMap<Vec<String>, u64> {
+    let mut words: HashMap<Vec<String>, u64> = HashMap::new();
+    for encoding in encodings {
+        let mut word: Vec<String> = Vec::new();
+        let mut word_id = None;
+        let tokens = encoding
+            .get_ids()
+            .iter()
+            .zip(encoding.get_tokens())
+            .zip(encoding.get_word_ids());
+        for ((id, token), id_of_word) in tokens {
+            let barrier = barriers.contains(id)
+                || (token.len() == 6 && token.starts_with("<0x") && token.ends_with('>'));
+            if barrier || *id_of_word != word_id {
+                if word.len() > 1 {
+                    *words.entry(std::mem::take(&mut word)).or_default() += 1;
+                }
+                word.clear();
+                word_id = *id_of_word;
+            }
+            if !barrier {
+                word.push(token.clone());
+            }
+        }
+        if word.len() > 1 {
+            *words.entry(word).or_default() += 1;
+        }
+    }
+    words
+}
+
+// Moves the added tokens that are not part of a serialized model into it,
+// so that they keep their ids once tokens are appended to the model. The
+// model and the added tokens must cover every id up to `end`
+fn absorb_added_tokens(
+    model: &mut Value,
+    added_tokens: &HashMap<u32, String>,
+    end: u32,
+) -> Result<(), ExTokenizersError> {
+    let missing = |id: u32| {
+        ExTokenizersError::Other(format!(
+            "the vocabulary has no token with id {id}, so tokens cannot be appended"
+        ))
+    };
+    if model["type"] == "Unigram" {
+        let min_score = model["vocab"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry[1].as_f64())
+            .reduce(f64::min)
+            .unwrap_or(0.0);
+        let vocab = model["vocab"]
+            .as_array_mut()
+            .ok_or_else(|| ExTokenizersError::Internal(String::from("invalid Unigram vocab")))?;
+        for id in vocab.len() as u32..end {
+            let token = added_tokens.get(&id).ok_or_else(|| missing(id))?;
+            vocab.push(serde_json::json!([token, min_score]));
+        }
+    } else {
+        let vocab = model["vocab"]
+            .as_object_mut()
+            .ok_or_else(|| ExTokenizersError::Internal(String::from("invalid model vocab")))?;
+        let ids: HashSet<u64> = vocab.values().filter_map(Value::as_u64).collect();
+        for id in 0..end {
+            if !ids.contains(&(id as u64)) {
+                let token = added_tokens.get(&id).ok_or_else(|| missing(id))?;
+                vocab.insert(token.clone(), Value::from(id));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(NifTaggedEnum)]
+pub enum ExtendOption {
+    MinFrequency(u64),
+    MaxTokenLength(Option<usize>),
+}
+
+#[derive(rustler::NifMap)]
+pub struct ExtendedVocabulary {
+    tokenizer: ExTokenizersTokenizer,
+    tokens: Vec<(String, u32)>,
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vocabulary_extend(
+    tokenizer: ExTokenizersTokenizer,
+    corpus: Vec<String>,
+    size: usize,
+    options: Vec<ExtendOption>,
+) -> Result<ExtendedVocabulary, ExTokenizersError> {
+    struct Opts {
+        min_frequency: u64,
+        max_token_length: Option<usize>,
+    }
+
+    // Default values
+    let mut opts = Opts {
+        min_frequency: 2,
+        max_token_length: None,
+    };
+
+    options.into_iter().for_each(|option| match option {
+        ExtendOption::MinFrequency(min_frequency) => opts.min_frequency = min_frequency,
+        ExtendOption::MaxTokenLength(max_token_length) => opts.max_token_length = max_token_length,
+    });
+
+    let old = &tokenizer.resource.0;
+    let mut json = serde_json::to_value(old)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    let model = &mut json["model"];
+    let model_type = model["type"].as_str().unwrap_or_default().to_owned();
+    if !matches!(model_type.as_str(), "BPE" | "WordPiece" | "Unigram") {
+        return Err(ExTokenizersError::Other(String::from(
+            "vocabulary extension requires a BPE, WordPiece or Unigram model",
+        )));
+    }
+
+    let added_tokens: HashMap<u32, String> = old
+        .get_added_tokens_decoder()
+        .into_iter()
+        .map(|(id, token)| (id, token.content))
+        .collect();
+    let mut barriers: HashSet<u32> = added_tokens.keys().copied().collect();
+    barriers.extend(
+        model_tokens(model, &[])
+            .iter()
+            .filter_map(|unk_token| old.token_to_id(unk_token)),
+    );
+
+    let encodings = encode_corpus(old, corpus, false)?;
+    let vocab: HashSet<String> = old.get_vocab(true).into_keys().collect();
+    let prefix = match model_type.as_str() {
+        "Unigram" => "",
+        _ => model["continuing_subword_prefix"].as_str().unwrap_or(""),
+    };
+    let learned = learn_tokens(
+        count_words(&encodings, &barriers),
+        &vocab,
+        prefix,
+        size,
+        opts.min_frequency,
+        opts.max_token_length,
+    );
+
+    // New Unigram pieces are scored within the range of the existing ones,
+    // leaving out the unknown piece whose score is not a log-probability
+    let unk_id = model["unk_id"].as_u64();
+    let (min_score, max_score) = model["vocab"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter(|(id, _)| unk_id != Some(*id as u64))
+        .filter_map(|(_, entry)| entry[1].as_f64())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), score| {
+            (min.min(score), max.max(score))
+        });
+
+    let end = vocab_end(old);
+    absorb_added_tokens(model, &added_tokens, end)?;
+    for (id, learned) in (end..).zip(&learned) {
+        match model_type.as_str() {
+            "Unigram" => {
+                let mut score = (learned.count as f64 / learned.total as f64).ln();
+                if min_score <= max_score {
+                    score = score.clamp(min_score, max_score);
+                }
+                if let Some(vocab) = model["vocab"].as_array_mut() {
+                    vocab.push(serde_json::json!([learned.token, score]));
+                }
+            }
+            _ => {
+                model["vocab"][&learned.token] = Value::from(id);
+                if let Some(merges) = model["merges"].as_array_mut() {
+                    merges.push(serde_json::json!([learned.left, learned.right]));
+                }
+            }
+        }
+    }
+
+    Ok(ExtendedVocabulary {
+        tokenizer: rebuild_tokenizer(json)?.into(),
+        tokens: (end..)
+            .zip(learned)
+            .map(|(id, learned)| (learned.token, id))
+            .collect(),
+    })
+}
+
+// Renames the token with the given id in a serialized model, if the model
+// has it. BPE tokens that are part of a merge cannot be renamed, since
+// merges produce tokens by concatenation
+fn rename_model_token(
+    model: &mut Value,
+    id: u32,
+    old: &str,
+    new: &str,
+) -> Result<(), ExTokenizersError> {
+    if model["type"] == "Unigram" {
+        if let Some(piece) = model["vocab"]
+            .get_mut(id as usize)
+            .and_then(|entry| entry.get_mut(0))
+        {
+            if piece.as_str() == Some(old) {
+                *piece = Value::from(new);
+            }
+        }
+        return Ok(());
+    }
+
+    if model["vocab"][old].as_u64() != Some(id as u64) {
+        return Ok(());
+    }
+    if model["type"] == "BPE" {
+        let prefix = model["continuing_subword_prefix"].as_str().unwrap_or("");
+        let merged = model["merges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|merge| Some((merge[0].as_str()?, merge[1].as_str()?)))
+            .any(|(left, right)| {
+                left == old
+                    || right == old
+                    || format!("{}{}", left, right.strip_prefix(prefix).unwrap_or(right)) == old
+            });
+        if merged {
+            return Err(ExTokenizersError::Other(format!(
+                "token {old:?} is part of the BPE merges and cannot be renamed"
+            )));
+        }
+    }
+    if let Some(vocab) = model["vocab"].as_object_mut() {
+        vocab.remove(old);
+        vocab.insert(new.to_owned(), Value::from(id));
+    }
+    if model["unk_token"] == old {
+        model["unk_token"] = Value::from(new);
+    }
+    Ok(())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vocabulary_rename(
+    tokenizer: ExTokenizersTokenizer,
+    id: u32,
+    content: String,
+) -> Result<ExTokenizersTokenizer, ExTokenizersError> {
+    let tokenizer = &tokenizer.resource.0;
+    let old = tokenizer
+        .id_to_token(id)
+        .ok_or_else(|| ExTokenizersError::UnknownToken(format!("no token with id {id}")))?;
+    if old == content {
+        return Ok(tokenizer.clone().into());
+    }
+    if tokenizer.token_to_id(&content).is_some() {
+        return Err(ExTokenizersError::Other(format!(
+            "token {content:?} is already in the vocabulary"
+        )));
+    }
+
+    let mut json = serde_json::to_value(tokenizer)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    for added_token in json["added_tokens"].as_array_mut().into_iter().flatten() {
+        if added_token["id"] == id {
+            added_token["content"] = Value::from(content.as_str());
+        }
+    }
+    rename_model_token(&mut json["model"], id, &old, &content)?;
+    special_token_ids(
+        &mut json["post_processor"],
+        "post_processor",
+        &mut |_, token, _| {
+            if *token == old {
+                *token = content.clone();
+            }
+        },
+    );
+    if let Some(pad_token) = json["padding"].get_mut("pad_token") {
+        if *pad_token == old.as_str() {
+            *pad_token = Value::from(content.as_str());
+        }
+    }
+
+    Ok(rebuild_tokenizer(json)?.into())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vocabulary_update_added_token(
+    tokenizer: ExTokenizersTokenizer,
+    id: u32,
+    options: Vec<AddedTokenOption>,
+) -> Result<ExTokenizersTokenizer, ExTokenizersError> {
+    let tokenizer = &tokenizer.resource.0;
+    let content = tokenizer
+        .id_to_token(id)
+        .ok_or_else(|| ExTokenizersError::UnknownToken(format!("no token with id {id}")))?;
+
+    let mut json = serde_json::to_value(tokenizer)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    let added_tokens = json["added_tokens"]
+        .as_array_mut()
+        .ok_or_else(|| ExTokenizersError::Internal(String::from("invalid added tokens")))?;
+    // Tokens of the model become added tokens
+    let index = match added_tokens.iter().position(|token| token["id"] == id) {
+        Some(index) => index,
+        None => {
+            let mut token = serde_json::to_value(AddedToken::from(content, false))
+                .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+            token["id"] = Value::from(id);
+            added_tokens.push(token);
+            added_tokens.len() - 1
+        }
+    };
+
+    let added_token = &mut added_tokens[index];
+    for option in options {
+        let (key, value) = match option {
+            AddedTokenOption::Special(value) => ("special", value),
+            AddedTokenOption::SingleWord(value) => ("single_word", value),
+            AddedTokenOption::Lstrip(value) => ("lstrip", value),
+            AddedTokenOption::Rstrip(value) => ("rstrip", value),
+            AddedTokenOption::Normalized(value) => ("normalized", value),
+        };
+        added_token[key] = Value::from(value);
+    }
+
+    Ok(rebuild_tokenizer(json)?.into())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vocabulary_remove_added_tokens(
+    tokenizer: ExTokenizersTokenizer,
+    ids: Vec<u32>,
+) -> Result<ExTokenizersTokenizer, ExTokenizersError> {
+    let tokenizer = &tokenizer.resource.0;
+    let added_tokens = tokenizer.get_added_tokens_decoder();
+    if let Some(id) = ids.iter().find(|id| !added_tokens.contains_key(id)) {
+        return Err(ExTokenizersError::Other(format!(
+            "no added token with id {id}"
+        )));
+    }
+
+    let mut json = serde_json::to_value(tokenizer)
+        .map_err(|error| ExTokenizersError::Internal(error.to_string()))?;
+    if let Some(tokens) = json["added_tokens"].as_array_mut() {
+        tokens.retain(|token| {
+            token["id"]
+                .as_u64()
+                .is_none_or(|id| !ids.contains(&(id as u32)))
+        });
+    }
+    let new = rebuild_tokenizer(json)?;
+
+    // Added tokens that are not part of the model get consecutive ids, so
+    // removing one of them may shift the others
+    for (id, token) in added_tokens {
+        if !ids.contains(&id) && new.token_to_id(&token.content) != Some(id) {
+            return Err(ExTokenizersError::Other(format!(
+                "removing the tokens would change the id of {:?}",
+                token.content
+            )));
+        }
+    }
+
+    Ok(new.into())
+}
@@ -0,0 +1,149 @@
+defmodule Tokenizers.EncodingTest do
+  use ExUnit.Case, async: true
+  doctest Tokenizers.Encoding
+
+  alias Tokenizers.Encoding
+  alias Tokenizers.Tokenizer
+
+  @labels ["O", "B-ORG", "I-ORG", "B-LOC", "I-LOC"]
+
+  setup do
+    {:ok, tokenizer} = Tokenizer.from_file("test/fixtures/bert-base-cased.json")
+    {:ok, tokenizer: tokenizer}
+  end
+
+  describe "aggregate_entities/5" do
+    test "merges label ids into entities", %{tokenizer: tokenizer} do
+      text = "Hugging Face Inc. is in Brooklyn"
+      {:ok, encoding} = Tokenizer.encode(tokenizer, text)
+
+      predictions = label_ids(encoding)
+
+      assert {:ok, entities} =
+               Encoding.aggregate_entities(encoding, text, predictions, @labels,
+                 predictions_type: :label_ids
+               )
+
+      assert [
+               %{label: "ORG", start: 0, end: 16, text: "Hugging Face Inc", score: 1.0},
+               %{label: "LOC", start: 24, end: 32, text: "Brooklyn", score: 1.0}
+             ] = entities
+    end
+
+    test "groups subwords using the aggregation strategy", %{tokenizer: tokenizer} do
+      text = "Hugging Face"
+      {:ok, encoding} = Tokenizer.encode(tokenizer, text)
+
+      # [CLS] Hu ##gging Face [SEP]
+      predictions =
+        scores([
+          [1.0, 0.0, 0.0, 0.0, 0.0],
+          [0.1, 0.6, 0.3, 0.0, 0.0],
+          [0.1, 0.0, 0.1, 0.8, 0.0],
+          [0.0, 0.0, 1.0, 0.0, 0.0],
+          [1.0, 0.0, 0.0, 0.0, 0.0]
+        ])
+
+      assert {:ok, [%{label: "ORG", text: "Hugging Face"}]} =
+               Encoding.aggregate_entities(encoding, text, predictions, @labels,
+                 aggregation_strategy: :first,
+                 softmax: false
+               )
+
+      assert {:ok, [%{label: "LOC", text: "Hugging"}, %{label: "ORG", text: "Face"}]} =
+               Encoding.aggregate_entities(encoding, text, predictions, @labels,
+                 aggregation_strategy: :max,
+                 softmax: false
+               )
+    end
+
+    test "aggregates across overflowing windows", %{tokenizer: tokenizer} do
+      text = "Hugging Face Inc. is in Brooklyn"
+      tokenizer = Tokenizer.set_truncation(tokenizer, max_length: 6, stride: 2)
+      {:ok, encoding} = Tokenizer.encode(tokenizer, text)
+
+      windows = [encoding | Encoding.get_overflowing(encoding)]
+      assert length(windows) > 1
+
+      predictions = windows |> Enum.map(&label_ids/1) |> Enum.join()
+
+      assert {:ok, entities} =
+               Encoding.aggregate_entities(windows, text, predictions, @labels,
+                 predictions_type: :label_ids
+               )
+
+      assert [%{label: "ORG", text: "Hugging Face Inc"}, %{label: "LOC", text: "Brooklyn"}] =
+               entities
+    end
+
+    test "returns an error when predictions do not match the tokens", %{tokenizer: tokenizer} do
+      {:ok, encoding} = Tokenizer.encode(tokenizer, "Hugging Face")
+
+      assert {:error, _} = Encoding.aggregate_entities(encoding, "Hugging Face", <<>>, @labels)
+    end
+  end
+
+  describe "question answering" do
+    setup %{tokenizer: tokenizer} do
+      context = "Hugging Face Inc. is a company based in Brooklyn, New York."
+
+      tokenizer =
+        Tokenizer.set_truncation(tokenizer, max_length: 12, stride: 2, strategy: :only_second)
+
+      {:ok, encoding} = Tokenizer.encode(tokenizer, {"Where is Hugging Face?", context})
+      {:ok, encoding: encoding, context: context}
+    end
+
+    test "maps an answer to token positions in every window", %{encoding: encoding} do
+      positions = Encoding.answer_token_positions(encoding, {40, 48})
+
+      assert length(positions) == length(Encoding.get_overflowing(encoding)) + 1
+
+      possible = for %{impossible: false, start: s, end: e} <- positions, do: {s, e}
+      assert possible == [{10, 10}, {9, 9}, {8, 8}]
+
+      assert %{start: 0, end: 0, impossible: true} in positions
+    end
+
+    test "finds the best answer span across windows", %{encoding: encoding, context: context} do
+      windows = [encoding | Encoding.get_overflowing(encoding)]
+      positions = Encoding.answer_token_positions(windows, {40, 48})
+
+      logits =
+        for {window, %{start: start}} <- Enum.zip(windows, positions), into: <<>> do
+          for i <- 0..(Encoding.get_length(window) - 1), into: <<>> do
+            logit = if i == start and start != 0, do: 10.0, else: 0.0
+            <<logit::native-float-32>>
+          end
+        end
+
+      assert {:ok, [%{text: "Brooklyn", start: 40, end: 48, score: 20.0}]} =
+               Encoding.answer_spans(windows, context, logits, logits)
+
+      assert {:ok, [%{text: "Brooklyn"}, %{text: ""}]} =
+               Encoding.answer_spans(windows, context, logits, logits,
+                 top_k: 2,
+                 max_answer_length: 1,
+                 handle_impossible_answer: true
+               )
+    end
+  end
+
+  defp label_ids(encoding) do
+    for token <- Encoding.get_tokens(encoding), into: <<>> do
+      id =
+        case token do
+          "Hu" -> 1
+          token when token in ["##gging", "Face", "Inc"] -> 2
+          "Brooklyn" -> 3
+          _ -> 0
+        end
+
+      <<id::native-unsigned-32>>
+    end
+  end
+
+  defp scores(rows) do
+    for row <- rows, score <- row, into: <<>>, do: <<score::native-float-32>>
+  end
+end
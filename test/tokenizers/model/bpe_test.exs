@@ -48,5 +48,69 @@ defmodule Tokenizers.Model.BPETest do
                  "test/fixtures/merges.txt"
                )
     end
+
+    @tag :tmp_dir
+    test "locates invalid entries of the vocabulary", config do
+      vocab = Path.join(config.tmp_dir, "vocab.json")
+      File.write!(vocab, ~s({"a": 0,\n "b": "x"}))
+
+      assert {:error, %Tokenizers.Error{kind: :json_parse, context: context}} =
+               Tokenizers.Model.BPE.from_file(vocab, "test/fixtures/merges.txt")
+
+      assert %{path: "b", line: 2} = context
+    end
+
+    @tag :tmp_dir
+    test "reports invalid lines of the merges file", config do
+      vocab = Path.join(config.tmp_dir, "vocab.json")
+      merges = Path.join(config.tmp_dir, "merges.txt")
+      File.write!(vocab, ~s({"a": 0, "b": 1, "c": 2, "ab": 3}))
+      File.write!(merges, "#version: 0.2\na b\nc")
+
+      assert {:error, %Tokenizers.Error{kind: :tokenizer, message: message}} =
+               Tokenizers.Model.BPE.from_file(vocab, merges)
+
+      assert message =~ "line 3"
+    end
+  end
+
+  describe "merges" do
+    setup do
+      {:ok, model} =
+        Tokenizers.Model.BPE.init(
+          %{"a" => 0, "b" => 1, "c" => 2, "ab" => 3, "abc" => 4, "[UNK]" => 5},
+          [{"a", "b"}, {"ab", "c"}],
+          unk_token: "[UNK]",
+          fuse_unk: true
+        )
+
+      %{model: model}
+    end
+
+    test "returns merges in rank order", %{model: model} do
+      assert Tokenizers.Model.BPE.merges(model) == {:ok, [{"a", "b"}, {"ab", "c"}]}
+    end
+
+    test "explains the merges applied to a word", %{model: model} do
+      assert {:ok, explanation} = Tokenizers.Model.BPE.explain(model, "abc")
+      assert explanation.symbols == ["a", "b", "c"]
+
+      assert explanation.merges == [
+               %{pair: {"a", "b"}, rank: 0, token: "ab"},
+               %{pair: {"ab", "c"}, rank: 1, token: "abc"}
+             ]
+
+      assert explanation.tokens == [%{id: 4, value: "abc", offsets: {0, 3}}]
+      assert explanation.fallbacks == []
+    end
+
+    test "reports fused unknown chars", %{model: model} do
+      assert {:ok, explanation} = Tokenizers.Model.BPE.explain(model, "abxxc")
+      assert Enum.map(explanation.tokens, & &1.value) == ["ab", "[UNK]", "c"]
+
+      assert explanation.fallbacks == [
+               %{kind: :unk, text: "xx", offsets: {2, 4}, tokens: ["[UNK]"]}
+             ]
+    end
   end
 end
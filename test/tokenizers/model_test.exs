@@ -29,4 +29,135 @@ defmodule Tokenizers.ModelTest do
       assert Tokenizers.Model.info(original_model) == Tokenizers.Model.info(loaded_model)
     end
   end
+
+  describe "model tokenization" do
+    setup do
+      {:ok, model} =
+        Tokenizers.Model.WordPiece.init(%{"[UNK]" => 0, "hug" => 1, "##ging" => 2, "face" => 3})
+
+      %{model: model}
+    end
+
+    test "tokenizes a single word", %{model: model} do
+      assert {:ok, [hug, ging]} = Tokenizers.Model.tokenize(model, "hugging")
+      assert hug == %{id: 1, value: "hug", offsets: {0, 3}}
+      assert ging == %{id: 2, value: "##ging", offsets: {3, 7}}
+    end
+
+    test "tokenizes a batch of words", %{model: model} do
+      assert {:ok, [[%{id: 3}], [%{id: 0, value: "[UNK]"}]]} =
+               Tokenizers.Model.tokenize_batch(model, ["face", "xyz"])
+    end
+
+    test "converts between tokens and ids", %{model: model} do
+      assert Tokenizers.Model.token_to_id(model, "face") == 3
+      assert Tokenizers.Model.token_to_id(model, "missing") == nil
+      assert Tokenizers.Model.id_to_token(model, 2) == "##ging"
+      assert Tokenizers.Model.id_to_token(model, 100) == nil
+    end
+
+    test "returns the vocabulary", %{model: model} do
+      assert Tokenizers.Model.get_vocab(model) ==
+               %{"[UNK]" => 0, "hug" => 1, "##ging" => 2, "face" => 3}
+
+      assert Tokenizers.Model.get_vocab_size(model) == 4
+    end
+  end
+
+  describe "Unigram scoring" do
+    setup do
+      vocab = [
+        {"<unk>", 0.0},
+        {"a", -2.0},
+        {"b", -2.5},
+        {"c", -2.2},
+        {"ab", -3.0},
+        {"bc", -3.5},
+        {"abc", -4.5}
+      ]
+
+      {:ok, model} = Tokenizers.Model.Unigram.init(vocab, unk_id: 0, byte_fallback: false)
+      %{model: model}
+    end
+
+    test "scores a text", %{model: model} do
+      assert {:ok, score} = Tokenizers.Model.Unigram.score(model, "abc")
+      assert score.pieces == [{"abc", -4.5}]
+      assert_in_delta score.viterbi, -4.5, 1.0e-6
+      assert_in_delta score.marginal, -3.8192960, 1.0e-6
+      assert_in_delta score.perplexity, :math.exp(4.5), 1.0e-6
+    end
+
+    test "scores a batch of texts", %{model: model} do
+      assert {:ok, [abc, empty, ab]} =
+               Tokenizers.Model.Unigram.score_batch(model, ["abc", "", "ab"])
+      assert abc.pieces == [{"abc", -4.5}]
+      assert empty == %{viterbi: 0.0, marginal: 0.0, perplexity: 1.0, pieces: []}
+      assert ab.pieces == [{"ab", -3.0}]
+    end
+
+    test "rejects other models" do
+      {:ok, model} = Tokenizers.Model.BPE.empty()
+      assert {:error, _} = Tokenizers.Model.Unigram.score(model, "abc")
+    end
+  end
+
+  describe "Unigram construction" do
+    @vocab [{"<unk>", 0.0}, {"a", -2.0}, {"b", -2.5}, {"ab", -3.0}]
+
+    test "init defaults to no unknown token and no byte fallback" do
+      assert {:ok, model} = Tokenizers.Model.Unigram.init(@vocab)
+      info = Tokenizers.Model.info(model)
+      assert info["unk_id"] == nil
+      assert info["byte_fallback"] == false
+    end
+
+    test "exports the vocabulary in id order" do
+      {:ok, model} = Tokenizers.Model.Unigram.init(@vocab, unk_id: 0)
+      assert Tokenizers.Model.Unigram.vocab(model) == {:ok, @vocab}
+    end
+
+    test "rejects vocabulary export from other models" do
+      {:ok, model} = Tokenizers.Model.BPE.empty()
+      assert {:error, _} = Tokenizers.Model.Unigram.vocab(model)
+    end
+
+    test "reports vocabulary size, unknown id and score range" do
+      {:ok, model} = Tokenizers.Model.Unigram.init(@vocab, unk_id: 0)
+
+      assert %{
+               "model_type" => "unigram",
+               "vocab_size" => 4,
+               "unk_id" => 0,
+               "min_score" => -3.0,
+               "max_score" => 0.0
+             } = Tokenizers.Model.info(model)
+    end
+
+    @tag :tmp_dir
+    test "loads a saved model from file", config do
+      {:ok, model} = Tokenizers.Model.Unigram.init(@vocab, unk_id: 0, byte_fallback: true)
+      {:ok, [path]} = Tokenizers.Model.save(model, config.tmp_dir)
+
+      assert {:ok, loaded} = Tokenizers.Model.Unigram.from_file(path)
+      assert Tokenizers.Model.Unigram.vocab(loaded) == {:ok, @vocab}
+      assert Tokenizers.Model.info(loaded) == Tokenizers.Model.info(model)
+    end
+
+    test "fails to load a missing file" do
+      assert {:error, _} = Tokenizers.Model.Unigram.from_file("test/fixtures/missing.json")
+    end
+
+    @tag :tmp_dir
+    test "locates invalid fields of the file", config do
+      path = Path.join(config.tmp_dir, "unigram.json")
+      json = ~s({"type": "Unigram", "unk_id": 0, "vocab": [["<unk>", 0.0], ["a", "x"]]})
+      File.write!(path, json)
+
+      assert {:error, %Tokenizers.Error{kind: :json_parse, context: context}} =
+               Tokenizers.Model.Unigram.from_file(path)
+
+      assert %{path: "vocab[1][1]", line: 1} = context
+    end
+  end
 end
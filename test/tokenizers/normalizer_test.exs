@@ -131,4 +131,42 @@ defmodule Tokenizers.NormalizerTest do
       assert length(Tokenizers.Normalizer.byte_level_alphabet()) != 0
     end
   end
+
+  describe "Alignments" do
+    test "maps normalized bytes and chars to the original input" do
+      normalizer = Tokenizers.Normalizer.bert_normalizer()
+
+      assert {:ok, %{normalized: "hello", alignments: alignments}} =
+               Tokenizers.Normalizer.normalize_with_alignments(normalizer, "Héllo")
+
+      assert alignments == [{0, 1}, {1, 3}, {3, 4}, {4, 5}, {5, 6}]
+
+      assert {:ok, %{normalized: "hello", alignments: alignments}} =
+               Tokenizers.Normalizer.normalize_with_alignments(normalizer, "Héllo", unit: :char)
+
+      assert alignments == [{0, 1}, {1, 2}, {2, 3}, {3, 4}, {4, 5}]
+    end
+
+    test "maps expanded chars to the same original range" do
+      {:ok, result} =
+        Tokenizers.Normalizer.nfkc()
+        |> Tokenizers.Normalizer.normalize_with_alignments("ﬁne", unit: :char)
+
+      assert result == %{normalized: "fine", alignments: [{0, 1}, {0, 1}, {1, 2}, {2, 3}]}
+    end
+
+    test "converts ranges between normalized and original coordinates" do
+      {:ok, result} =
+        Tokenizers.Normalizer.bert_normalizer()
+        |> Tokenizers.Normalizer.normalize_with_alignments("Héllo World")
+
+      assert Tokenizers.Normalizer.to_original(result, {1, 5}) == {1, 6}
+      assert Tokenizers.Normalizer.to_original(result, {2, 2}) == {3, 3}
+      assert Tokenizers.Normalizer.to_original(result, {0, 20}) == nil
+
+      assert Tokenizers.Normalizer.to_normalized(result, {0, 6}) == {0, 5}
+      assert Tokenizers.Normalizer.to_normalized(result, {7, 12}) == {6, 11}
+      assert Tokenizers.Normalizer.to_normalized(result, {2, 2}) == {2, 2}
+    end
+  end
 end
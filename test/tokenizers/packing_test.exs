@@ -0,0 +1,56 @@
+defmodule Tokenizers.PackingTest do
+  use ExUnit.Case, async: true
+  doctest Tokenizers.Packing
+
+  alias Tokenizers.Packing
+
+  describe "pack/3" do
+    test "packs documents greedily in order" do
+      assert {:ok, packed} = Packing.pack([[1, 2, 3], [4, 5], [6, 7]], 6, separator_id: 9)
+
+      assert packed.shape == {2, 6}
+      assert u32_to_list(packed.ids) == [1, 2, 3, 9, 0, 0, 4, 5, 9, 6, 7, 9]
+      assert u32_to_list(packed.attention_mask) == [1, 1, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1]
+      assert u32_to_list(packed.position_ids) == [0, 1, 2, 3, 0, 0, 0, 1, 2, 0, 1, 2]
+      assert u32_to_list(packed.segment_ids) == [1, 1, 1, 1, 0, 0, 1, 1, 1, 2, 2, 2]
+
+      assert [
+               %{document: 0, offset: 0, row: 0, start: 0, length: 4},
+               %{document: 1, offset: 0, row: 1, start: 0, length: 3},
+               %{document: 2, offset: 0, row: 1, start: 3, length: 3}
+             ] = packed.spans
+    end
+
+    test "packs documents with first fit decreasing" do
+      documents = [[1, 2], [3, 4, 5, 6], [7, 8, 9], [10]]
+
+      assert {:ok, packed} = Packing.pack(documents, 5, strategy: :first_fit_decreasing)
+
+      assert packed.shape == {2, 5}
+      assert u32_to_list(packed.ids) == [3, 4, 5, 6, 10, 7, 8, 9, 1, 2]
+    end
+
+    test "splits documents longer than a row" do
+      assert {:ok, packed} = Packing.pack([[1, 2, 3, 4, 5]], 2, pad_id: 7)
+
+      assert packed.shape == {3, 2}
+      assert u32_to_list(packed.ids) == [1, 2, 3, 4, 5, 7]
+      assert Enum.map(packed.spans, & &1.offset) == [0, 2, 4]
+    end
+
+    test "accepts encodings" do
+      {:ok, tokenizer} = Tokenizers.Tokenizer.from_file("test/fixtures/bert-base-cased.json")
+      {:ok, encoding} = Tokenizers.Tokenizer.encode(tokenizer, "Hello world")
+
+      ids = Tokenizers.Encoding.get_ids(encoding)
+
+      assert {:ok, packed} = Packing.pack([encoding, encoding], 8)
+      assert packed.shape == {1, 8}
+      assert u32_to_list(packed.ids) == ids ++ ids
+    end
+  end
+
+  defp u32_to_list(binary) do
+    for <<x::native-unsigned-32 <- binary>>, do: x
+  end
+end
@@ -34,6 +34,21 @@ defmodule Tokenizers.PostProcessorTest do
     end
   end
 
+  describe "template" do
+    test "raises on malformed templates" do
+      error =
+        assert_raise Tokenizers.Error, fn ->
+          Tokenizers.PostProcessor.template(single: "[CLS] $A:x")
+        end
+
+      assert error.kind == :invalid_template
+
+      assert_raise Tokenizers.Error, ~r/\[SEP\]/, fn ->
+        Tokenizers.PostProcessor.template(single: "$A [SEP]", special_tokens: [])
+      end
+    end
+  end
+
   describe "robertaProcessing" do
     test "instantiates correctly with only two parameters" do
       assert %Tokenizers.PostProcessor{} =
@@ -23,6 +23,24 @@ defmodule Tokenizers.TokenizerTest do
       {:ok, tokenizer} = Tokenizer.from_file(path)
       assert Tokenizer.get_vocab_size(tokenizer) == 28996
     end
+
+    test "returns a structured error for a missing file" do
+      assert {:error, %Tokenizers.Error{kind: :io}} =
+               Tokenizer.from_file("test/fixtures/missing.json")
+    end
+
+    test "locates invalid fields of the file" do
+      json =
+        "test/fixtures/bert-base-cased.json"
+        |> File.read!()
+        |> String.replace(~s("id": 0,), ~s("id": "x",), global: false)
+
+      assert {:error, %Tokenizers.Error{kind: :json_parse, context: context} = error} =
+               Tokenizer.from_buffer(json)
+
+      assert %{path: "added_tokens[0].id", line: 7, column: 15} = context
+      assert Exception.message(error) =~ "expected u32"
+    end
   end
 
   describe "modify tokenizer" do
@@ -188,6 +206,526 @@ defmodule Tokenizers.TokenizerTest do
                )
     end
 
+    test "can flatten overflowing windows", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.set_truncation(tokenizer, max_length: 6, stride: 2)
+
+      {:ok, [first, second]} =
+        Tokenizer.encode_batch(tokenizer, ["Hugging Face Inc. is in Brooklyn", "Hello world"])
+
+      windows = [first | Encoding.get_overflowing(first)] ++ [second]
+
+      assert {:ok, flat} =
+               Tokenizer.encode_batch(
+                 tokenizer,
+                 ["Hugging Face Inc. is in Brooklyn", "Hello world"],
+                 overflowing: :flat
+               )
+
+      assert flat.overflow_to_sample_mapping == [0, 0, 0, 1]
+      assert flat.window_index == [0, 1, 2, 0]
+
+      assert Enum.map(flat.encodings, &Encoding.get_ids/1) ==
+               Enum.map(windows, &Encoding.get_ids/1)
+
+      assert Enum.all?(flat.encodings, &(Encoding.get_overflowing(&1) == []))
+    end
+
+    test "can flatten overflowing windows into binaries", %{tokenizer: tokenizer} do
+      tokenizer =
+        tokenizer
+        |> Tokenizer.set_truncation(max_length: 6, stride: 2)
+        |> Tokenizer.set_padding(strategy: {:fixed, 6})
+
+      assert {:ok, binaries} =
+               Tokenizer.encode(tokenizer, "Hugging Face Inc. is in Brooklyn",
+                 overflowing: :binaries
+               )
+
+      assert binaries.shape == {3, 6}
+      assert byte_size(binaries.ids) == 3 * 6 * 4
+      assert binaries.overflow_to_sample_mapping == list_to_u32([0, 0, 0])
+      assert binaries.window_index == list_to_u32([0, 1, 2])
+
+      assert {:error, _} =
+               Tokenizer.encode_batch(
+                 Tokenizer.disable_padding(tokenizer),
+                 ["Hugging Face Inc. is in Brooklyn", "Hello"],
+                 overflowing: :binaries
+               )
+    end
+
+    test "can group a batch by length", %{tokenizer: tokenizer} do
+      # 5, 13, 4 and 7 tokens with special tokens
+      inputs = [
+        "Hello world there",
+        "Hugging Face Inc. is a company based in Brooklyn",
+        "Hello world",
+        "This is a longer test"
+      ]
+
+      assert {:ok, buckets} = Tokenizer.encode_bucketed(tokenizer, inputs, 16)
+
+      assert buckets.indices == [[2, 0], [3], [1]]
+      assert buckets.batch_lengths == [5, 7, 13]
+      assert buckets.restore_order == [1, 3, 0, 2]
+
+      for {batch, length} <- Enum.zip(buckets.batches, buckets.batch_lengths),
+          encoding <- batch do
+        assert Encoding.get_length(encoding) == length
+      end
+
+      assert {:ok, %{batches: nil, indices: [[2], [0], [3], [1]], batch_lengths: lengths}} =
+               Tokenizer.encode_bucketed(tokenizer, inputs, 16,
+                 max_batch_size: 1,
+                 pad_to_multiple_of: 4,
+                 return_encodings: false
+               )
+
+      assert lengths == [4, 8, 8, 16]
+    end
+
+    test "can encode a conversation with a loss mask", %{tokenizer: tokenizer} do
+      segments = [{"user", "Hello world"}, {"assistant", "Hugging Face"}]
+      templates = %{"assistant" => {"[MASK]", "[SEP]"}}
+
+      assert {:ok, chat} = Tokenizer.encode_chat(tokenizer, segments, templates: templates)
+
+      assert Encoding.get_tokens(chat.encoding) ==
+               ~w([CLS] Hello world [MASK] Hu ##gging Face [SEP] [SEP])
+
+      assert chat.loss_mask == list_to_u32([0, 0, 0, 0, 1, 1, 1, 1, 0])
+
+      assert Encoding.get_offsets(chat.encoding) ==
+               [{0, 0}, {0, 5}, {6, 11}, {11, 17}, {17, 19}, {19, 24}, {25, 29}, {29, 34}, {0, 0}]
+
+      assert Encoding.get_word_ids(chat.encoding) == [nil, 0, 1, 2, 3, 3, 4, 5, nil]
+
+      assert [-100, -100, -100, -100 | _] =
+               for(<<label::native-signed-32 <- chat.labels>>, do: label)
+
+      assert [
+               %{segment: 0, role: "user", start: 1, end: 3},
+               %{segment: 1, role: "assistant", start: 3, end: 8}
+             ] = chat.turns
+
+      assert {:ok, chat} =
+               Tokenizer.encode_chat(tokenizer, [{"user", "Hello world", true}], ignore_index: -1)
+
+      assert chat.loss_mask == list_to_u32([0, 1, 1, 0])
+    end
+
+    test "adds a prefix space only at the start of chat turns" do
+      vocab = %{"<unk>" => 0, "▁hello" => 1, "world" => 2, "▁world" => 3}
+      {:ok, model} = Tokenizers.Model.WordLevel.init(vocab, unk_token: "<unk>")
+      {:ok, tokenizer} = Tokenizer.init(model)
+      tokenizer = Tokenizer.set_pre_tokenizer(tokenizer, Tokenizers.PreTokenizer.metaspace())
+      templates = %{"user" => {"hello", "world"}}
+
+      assert {:ok, chat} =
+               Tokenizer.encode_chat(tokenizer, [{"user", " world"}], templates: templates)
+
+      assert Encoding.get_ids(chat.encoding) == [1, 3, 2]
+      assert Encoding.get_offsets(chat.encoding) == [{0, 5}, {5, 11}, {11, 16}]
+      assert Encoding.get_word_ids(chat.encoding) == [0, 1, 2]
+    end
+
+    test "drops earlier turns to fit the max length", %{tokenizer: tokenizer} do
+      segments = [{"system", "Be nice"}, {"user", "Hello world"}, {"assistant", "Hugging Face"}]
+      templates = %{"assistant" => {"[MASK]", "[SEP]"}}
+
+      assert {:ok, chat} =
+               Tokenizer.encode_chat(tokenizer, segments,
+                 templates: templates,
+                 max_length: 9,
+                 keep_first: 1
+               )
+
+      assert Encoding.get_tokens(chat.encoding) ==
+               ~w([CLS] Be nice [MASK] Hu ##gging Face [SEP] [SEP])
+
+      assert [%{segment: 0}, %{segment: 2}] = chat.turns
+
+      assert {:ok, chat} = Tokenizer.encode_chat(tokenizer, segments, max_length: 4)
+      assert Encoding.get_tokens(chat.encoding) == ~w([CLS] Hu ##gging [SEP])
+      assert [%{segment: 2, start: 1, end: 3}] = chat.turns
+    end
+
+    test "can encode more than two sequences", %{tokenizer: tokenizer} do
+      sequences = ["Where is it?", "Brooklyn", "Hugging Face Inc"]
+      template = "[CLS] $A [SEP] $B [SEP] $C [SEP]"
+
+      assert {:ok, encoding} = Tokenizer.encode_multi(tokenizer, sequences, template: template)
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Where is it ? [SEP] Brooklyn [SEP] Hu ##gging Face Inc [SEP])
+
+      assert Encoding.get_type_ids(encoding) == [0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 2, 2, 2]
+
+      assert Encoding.get_sequence_ids(encoding) ==
+               [nil, 0, 0, 0, 0, nil, 1, nil, 2, 2, 2, 2, nil]
+
+      assert {:ok, encoding} =
+               Tokenizer.encode_multi(tokenizer, sequences,
+                 template: "[CLS] $A [SEP] $B:0 [SEP] $C:1 [SEP]:1"
+               )
+
+      assert Encoding.get_type_ids(encoding) == [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1]
+
+      assert {:error, _} = Tokenizer.encode_multi(tokenizer, sequences, template: "$A $B")
+      assert {:error, _} = Tokenizer.encode_multi(tokenizer, sequences, template: "$A $B $C [X]")
+    end
+
+    test "truncates sequences by budget and priority", %{tokenizer: tokenizer} do
+      sequences = ["Where is it?", "Brooklyn", "Hugging Face Inc"]
+      template = "[CLS] $A [SEP] $B [SEP] $C [SEP]"
+
+      assert {:ok, encoding} =
+               Tokenizer.encode_multi(tokenizer, sequences,
+                 template: template,
+                 max_length: 10,
+                 priorities: [2, 1, 0]
+               )
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Where is it ? [SEP] Brooklyn [SEP] Hu [SEP])
+
+      assert {:ok, encoding} =
+               Tokenizer.encode_multi(tokenizer, sequences,
+                 template: template,
+                 max_length: 10,
+                 priorities: [0, 1, 2]
+               )
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Where [SEP] Brooklyn [SEP] Hu ##gging Face Inc [SEP])
+
+      assert {:ok, encoding} = Tokenizer.encode_multi(tokenizer, sequences, budgets: [2, nil, 1])
+
+      assert Encoding.get_tokens(encoding) == ~w(Where is Brooklyn Hu)
+    end
+
+    test "can expand placeholders", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.add_special_tokens(tokenizer, ["<image>"])
+
+      assert {:ok, %{encoding: encoding, media_mask: media_mask}} =
+               Tokenizer.encode_with_placeholders(tokenizer, "Hello <image> world", %{
+                 "<image>" => 3
+               })
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Hello <image> <image> <image> world [SEP])
+
+      assert media_mask == list_to_u32([0, 0, 1, 1, 1, 0, 0])
+      assert Encoding.get_word_ids(encoding) == [nil, 0, 1, 1, 1, 2, nil]
+      assert Enum.slice(Encoding.get_offsets(encoding), 2..4) == [{6, 13}, {6, 13}, {6, 13}]
+
+      hello = Tokenizer.token_to_id(tokenizer, "Hello")
+      world = Tokenizer.token_to_id(tokenizer, "world")
+
+      assert {:ok, %{encoding: encoding, media_mask: media_mask}} =
+               Tokenizer.encode_with_placeholders(tokenizer, "Hello <image> world <image>", %{
+                 "<image>" => [2, [hello, world]]
+               })
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Hello <image> <image> world Hello world [SEP])
+
+      assert media_mask == list_to_u32([0, 0, 1, 1, 0, 1, 1, 0])
+
+      assert {:error, _} =
+               Tokenizer.encode_with_placeholders(tokenizer, "<image> <image>", %{
+                 "<image>" => [1]
+               })
+
+      assert {:error, _} =
+               Tokenizer.encode_with_placeholders(tokenizer, "Hello", %{"<video>" => 1})
+    end
+
+    test "expands placeholders before truncation", %{tokenizer: tokenizer} do
+      tokenizer =
+        tokenizer
+        |> Tokenizer.add_special_tokens(["<image>"])
+        |> Tokenizer.set_truncation(max_length: 5)
+
+      assert {:ok, %{encoding: encoding, media_mask: media_mask}} =
+               Tokenizer.encode_with_placeholders(tokenizer, "Hello <image> world", %{
+                 "<image>" => 3
+               })
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello <image> <image> [SEP])
+      assert media_mask == list_to_u32([0, 0, 1, 1, 0])
+    end
+
+    test "matches placeholders when encoding special tokens", %{tokenizer: tokenizer} do
+      tokenizer =
+        tokenizer
+        |> Tokenizer.add_special_tokens(["<image>"])
+        |> Tokenizer.set_encode_special_tokens(true)
+
+      text = "Hello [SEP] <image> world"
+
+      assert {:ok, %{encoding: encoding, media_mask: media_mask}} =
+               Tokenizer.encode_with_placeholders(tokenizer, text, %{"<image>" => 2})
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Hello [ SE ##P ] <image> <image> world [SEP])
+
+      assert media_mask == list_to_u32([0, 0, 0, 0, 0, 0, 1, 1, 0, 0])
+
+      hello = Tokenizer.token_to_id(tokenizer, "Hello")
+
+      assert {:ok, %{encoding: encoding}} =
+               Tokenizer.encode_with_placeholders(tokenizer, text, %{"<image>" => [[hello]]})
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello [ SE ##P ] Hello world [SEP])
+    end
+
+    test "marks expanded placeholders of both sequences", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.add_special_tokens(tokenizer, ["<image>"])
+
+      assert {:ok, %{encoding: encoding, media_mask: media_mask}} =
+               Tokenizer.encode_with_placeholders(
+                 tokenizer,
+                 {"Hello <image>", "<image> world"},
+                 %{"<image>" => 2}
+               )
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Hello <image> <image> [SEP] <image> <image> world [SEP])
+
+      assert media_mask == list_to_u32([0, 0, 1, 1, 0, 1, 1, 0, 0])
+      assert Encoding.get_word_ids(encoding) == [nil, 0, 1, 1, nil, 0, 0, 1, nil]
+    end
+
+    test "can encode special tokens as ordinary text", %{tokenizer: tokenizer} do
+      text = "Hello [SEP] world"
+
+      assert {:ok, encoding} = Tokenizer.encode(tokenizer, text)
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello [SEP] world [SEP])
+
+      assert {:ok, encoding} = Tokenizer.encode(tokenizer, text, encode_special_tokens: true)
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello [ SE ##P ] world [SEP])
+
+      sanitized = Tokenizer.set_encode_special_tokens(tokenizer, true)
+      assert Tokenizer.get_encode_special_tokens(sanitized)
+      refute Tokenizer.get_encode_special_tokens(tokenizer)
+
+      assert {:ok, encoding} = Tokenizer.encode(sanitized, text)
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello [ SE ##P ] world [SEP])
+
+      assert {:ok, [encoding]} =
+               Tokenizer.encode_batch(tokenizer, [{text, text}],
+                 encode_special_tokens: [false, true]
+               )
+
+      assert Encoding.get_tokens(encoding) ==
+               ~w([CLS] Hello [SEP] world [SEP] Hello [ SE ##P ] world [SEP])
+
+      assert Encoding.get_type_ids(encoding) == [0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1]
+    end
+
+    test "can encode special tokens as ordinary text per role", %{tokenizer: tokenizer} do
+      segments = [{"system", "Be nice [SEP]"}, {"user", "Hello [SEP]"}]
+
+      assert {:ok, chat} =
+               Tokenizer.encode_chat(tokenizer, segments,
+                 templates: %{"user" => {"[MASK]", ""}},
+                 encode_special_tokens: ["user"]
+               )
+
+      assert Encoding.get_tokens(chat.encoding) ==
+               ~w([CLS] Be nice [SEP] [MASK] Hello [ SE ##P ] [SEP])
+    end
+
+    test "can encode with protected spans", %{tokenizer: tokenizer} do
+      assert {:ok, encoding} =
+               Tokenizer.encode(tokenizer, "HuggingFace rocks", protected_spans: [{0, 7}])
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hu ##gging Face rocks [SEP])
+      assert Encoding.get_offsets(encoding) == [{0, 0}, {0, 2}, {2, 7}, {7, 11}, {12, 17}, {0, 0}]
+      assert Encoding.get_word_ids(encoding) == [nil, 0, 0, 1, 2, nil]
+
+      text = "Visit https://example.com now"
+      spans = [{"https://example.com", 103}]
+
+      assert {:ok, encoding} = Tokenizer.encode(tokenizer, text, protected_spans: spans)
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] V ##isi ##t [MASK] now [SEP])
+      assert Encoding.get_ids(encoding) == [101, 159, 26868, 1204, 103, 1208, 102]
+      assert Enum.at(Encoding.get_offsets(encoding), 4) == {6, 25}
+
+      assert {:ok, [encoding]} = Tokenizer.encode_batch(tokenizer, [text], protected_spans: spans)
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] V ##isi ##t [MASK] now [SEP])
+
+      assert {:error, _} =
+               Tokenizer.encode(tokenizer, "HuggingFace", protected_spans: [{0, 7}, {5, 9}])
+
+      assert {:error, _} = Tokenizer.encode_batch(tokenizer, [text], protected_spans: [{0, 5}])
+
+      assert {:error, %Tokenizers.Error{kind: :unknown_token}} =
+               Tokenizer.encode(tokenizer, text, protected_spans: [{"now", 1_000_000}])
+
+      assert {:error, _} =
+               Tokenizer.encode(tokenizer, {"Hello", "world"}, protected_spans: [{0, 5}])
+
+      assert {:ok, encoding} =
+               Tokenizer.encode(tokenizer, {"Hello", "my world"}, protected_spans: ["world"])
+
+      assert Encoding.get_tokens(encoding) == ~w([CLS] Hello [SEP] my world [SEP])
+    end
+
+    test "adds a prefix space only at the start of protected sequences" do
+      vocab = %{"<unk>" => 0, "▁hello" => 1, "world" => 2, "▁world" => 3}
+      {:ok, model} = Tokenizers.Model.WordLevel.init(vocab, unk_token: "<unk>")
+      {:ok, tokenizer} = Tokenizer.init(model)
+      tokenizer = Tokenizer.set_pre_tokenizer(tokenizer, Tokenizers.PreTokenizer.metaspace())
+
+      assert {:ok, encoding} = Tokenizer.encode(tokenizer, "hello world")
+      assert Encoding.get_ids(encoding) == [1, 3]
+
+      assert {:ok, encoding} =
+               Tokenizer.encode(tokenizer, "hello world", protected_spans: [" world"])
+
+      assert Encoding.get_ids(encoding) == [1, 3]
+
+      assert {:ok, encoding} =
+               Tokenizer.encode(tokenizer, "helloworld", protected_spans: ["world"])
+      assert Encoding.get_ids(encoding) == [1, 2]
+    end
+
+    test "can trace the pipeline stages", %{tokenizer: tokenizer} do
+      assert {:ok, trace} = Tokenizer.trace(tokenizer, "Héllo [SEP] you")
+
+      assert [sequence] = trace.sequences
+
+      assert [
+               %{text: "Héllo ", original: {0, 7}, normalized: {0, 7}, added_token: nil},
+               %{text: "[SEP]", original: {7, 12}, normalized: {7, 12}, added_token: 102},
+               %{text: " you", original: {12, 16}, normalized: {12, 16}, added_token: nil}
+             ] = sequence.added_tokens
+
+      assert sequence.normalized.normalized == "Héllo [SEP] you"
+      assert Enum.take(sequence.normalized.alignments, 3) == [{0, 1}, {1, 3}, {1, 3}]
+      assert length(sequence.normalized.alignments) == byte_size("Héllo [SEP] you")
+
+      assert [
+               %{text: "Héllo", original: {0, 6}, normalized: {0, 6}},
+               %{text: "[SEP]", original: {7, 12}, normalized: {7, 12}},
+               %{text: "you", original: {13, 16}, normalized: {13, 16}}
+             ] = sequence.pre_tokens
+
+      assert [
+               %{text: "Héllo", tokens: ["H", "##é", "##llo"]},
+               %{text: "[SEP]", tokens: ["[SEP]"], ids: [102]},
+               %{text: "you", tokens: ["you"]}
+             ] = sequence.model
+
+      assert [%{index: 0, token: "[CLS]"}, %{index: 6, token: "[SEP]"}] = trace.post_processor
+      assert Encoding.get_tokens(trace.encoding) == ~w([CLS] H ##é ##llo [SEP] you [SEP])
+
+      assert {:ok, %{sequences: [_, _], post_processor: [], encoding: traced}} =
+               Tokenizer.trace(tokenizer, {"Hello", "you"}, add_special_tokens: false)
+
+      assert {:ok, encoding} =
+               Tokenizer.encode(tokenizer, {"Hello", "you"}, add_special_tokens: false)
+
+      assert Encoding.get_type_ids(traced) == [0, 1]
+      assert Encoding.get_type_ids(traced) == Encoding.get_type_ids(encoding)
+    end
+
+    test "can encode the n best segmentations", %{tokenizer: tokenizer} do
+      unigram = unigram_tokenizer()
+
+      assert {:ok, [first, second, third]} = Tokenizer.encode_nbest(unigram, "abc ab", 3)
+      assert Encoding.get_tokens(first.encoding) == ["abc", "ab"]
+      assert Encoding.get_tokens(second.encoding) == ["ab", "c", "ab"]
+      assert Encoding.get_tokens(third.encoding) == ["a", "bc", "ab"]
+      assert Encoding.get_offsets(third.encoding) == [{0, 1}, {1, 3}, {4, 6}]
+      assert_in_delta first.score, -7.5, 1.0e-6
+      assert_in_delta second.score, -8.2, 1.0e-6
+      assert_in_delta third.score, -8.5, 1.0e-6
+
+      assert {:error, _} = Tokenizer.encode_nbest(tokenizer, "Hello", 2)
+    end
+
+    test "can sample segmentations", %{tokenizer: tokenizer} do
+      unigram = unigram_tokenizer()
+      text = "abc abc abc abc"
+
+      {:ok, first} = Tokenizer.encode(unigram, text, sampling: [alpha: 0.1, seed: 1])
+      {:ok, second} = Tokenizer.encode(unigram, text, sampling: [alpha: 0.1, seed: 1])
+      assert Encoding.get_ids(first) == Encoding.get_ids(second)
+      assert Encoding.get_tokens(first) |> Enum.join() == "abcabcabcabc"
+
+      {:ok, encoding} = Tokenizer.encode(unigram, text, sampling: [alpha: 1000.0, seed: 1])
+      assert Encoding.get_tokens(encoding) == ["abc", "abc", "abc", "abc"]
+
+      # Each row has its own random stream
+      {:ok, [first, _]} = Tokenizer.encode_batch(unigram, [text, "ab"], sampling: [seed: 2])
+      {:ok, [alone]} = Tokenizer.encode_batch(unigram, [text], sampling: [seed: 2])
+      assert Encoding.get_ids(first) == Encoding.get_ids(alone)
+
+      assert {:error, _} = Tokenizer.encode(tokenizer, "Hello", sampling: [seed: 1])
+    end
+
+    test "can apply seeded BPE dropout", %{tokenizer: tokenizer} do
+      {:ok, model} =
+        Tokenizers.Model.BPE.init(
+          %{"a" => 0, "b" => 1, "c" => 2, "ab" => 3, "abc" => 4},
+          [{"a", "b"}, {"ab", "c"}]
+        )
+
+      {:ok, bpe} = Tokenizer.init(model)
+      bpe = Tokenizer.set_pre_tokenizer(bpe, Tokenizers.PreTokenizer.whitespace_split())
+      text = "abc abc abc abc"
+
+      {:ok, encoding} = Tokenizer.encode(bpe, text, dropout: [probability: 0.0])
+      assert Encoding.get_tokens(encoding) == ["abc", "abc", "abc", "abc"]
+
+      {:ok, encoding} = Tokenizer.encode(bpe, "abc", dropout: [probability: 1.0])
+      assert Encoding.get_tokens(encoding) == ["a", "b", "c"]
+
+      {:ok, first} = Tokenizer.encode(bpe, text, dropout: [probability: 0.5, seed: 3])
+      {:ok, second} = Tokenizer.encode(bpe, text, dropout: [probability: 0.5, seed: 3])
+      assert Encoding.get_ids(first) == Encoding.get_ids(second)
+      assert Encoding.get_tokens(first) |> Enum.join() == "abcabcabcabc"
+
+      # Each row has its own random stream
+      {:ok, [first, _]} = Tokenizer.encode_batch(bpe, [text, "ab"], dropout: [seed: 4])
+      {:ok, [alone]} = Tokenizer.encode_batch(bpe, [text], dropout: [seed: 4])
+      assert Encoding.get_ids(first) == Encoding.get_ids(alone)
+
+      assert {:error, _} = Tokenizer.encode(tokenizer, "Hello", dropout: [seed: 1])
+    end
+
+    test "keeps words whole with BPE dropout when merges are ignored" do
+      {:ok, bpe} =
+        Tokenizer.from_buffer("""
+        {
+          "version": "1.0",
+          "added_tokens": [],
+          "pre_tokenizer": {"type": "WhitespaceSplit"},
+          "model": {
+            "type": "BPE",
+            "ignore_merges": true,
+            "vocab": {"a": 0, "b": 1, "c": 2, "ab": 3, "abc": 4},
+            "merges": [["a", "b"]]
+          }
+        }
+        """)
+
+      text = "abc ab cab"
+      {:ok, encoding} = Tokenizer.encode(bpe, text)
+      {:ok, dropped} = Tokenizer.encode(bpe, text, dropout: [probability: 0.0])
+      assert Encoding.get_tokens(dropped) == ["abc", "ab", "c", "ab"]
+      assert Encoding.get_ids(dropped) == Encoding.get_ids(encoding)
+      assert Encoding.get_offsets(dropped) == Encoding.get_offsets(encoding)
+
+      {:ok, dropped} = Tokenizer.encode(bpe, "abc", dropout: [probability: 1.0])
+      assert Encoding.get_tokens(dropped) == ["abc"]
+    end
+
     test "can decode a single encoding", %{tokenizer: tokenizer} do
       text = "This is a test"
       {:ok, encoding} = Tokenizer.encode(tokenizer, text)
@@ -265,7 +803,171 @@ defmodule Tokenizers.TokenizerTest do
     end
   end
 
+  describe "diff/3" do
+    test "finds no difference between equal tokenizers", %{tokenizer: tokenizer} do
+      assert {:ok, diff} = Tokenizer.diff(tokenizer, tokenizer, corpus: ["Hello world"])
+      assert diff.equal
+      assert diff.config == []
+      assert diff.samples == []
+    end
+
+    test "reports configuration differences", %{tokenizer: tokenizer} do
+      other =
+        tokenizer
+        |> Tokenizer.set_normalizer(Tokenizers.Normalizer.bert_normalizer(lowercase: true))
+        |> Tokenizer.set_truncation(max_length: 8)
+
+      assert {:ok, diff} = Tokenizer.diff(tokenizer, other)
+      refute diff.equal
+
+      assert %{path: "normalizer.lowercase", left: "false", right: "true"} in diff.config
+      assert %{path: "truncation", left: nil, right: _} = List.last(diff.config)
+    end
+
+    test "reports vocabulary and added token differences", %{tokenizer: tokenizer} do
+      {:ok, other} = Tokenizers.Vocabulary.rename(tokenizer, 1, "<tool>")
+      {:ok, other} = Tokenizers.Vocabulary.update_added_token(other, 103, lstrip: true)
+
+      assert {:ok, diff} = Tokenizer.diff(tokenizer, other)
+
+      assert diff.vocab == %{added: [{"<tool>", 1}], removed: [{"[unused1]", 1}], changed: []}
+
+      assert diff.added_tokens.changed == [
+               %{content: "[MASK]", flag: :lstrip, left: false, right: true}
+             ]
+    end
+
+    test "reports differently encoded inputs", %{tokenizer: tokenizer} do
+      other = Tokenizer.set_normalizer(tokenizer, Tokenizers.Normalizer.lowercase())
+      corpus = ["hello", "Hello", "world", "World"]
+
+      assert {:ok, diff} = Tokenizer.diff(tokenizer, other, corpus: corpus, max_samples: 1)
+
+      assert [%{index: 1, input: "Hello", left: left, right: right}] = diff.samples
+      assert left != right
+    end
+  end
+
+  describe "validate/1" do
+    test "finds no issue in a consistent tokenizer", %{tokenizer: tokenizer} do
+      assert {:ok, []} = Tokenizer.validate(tokenizer)
+    end
+
+    test "reports a padding token with a wrong id", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.set_padding(tokenizer, pad_id: 5, pad_token: "[PAD]")
+
+      assert {:ok, [%{severity: :error, rule: :pad_id_mismatch, path: "padding.pad_id"}]} =
+               Tokenizer.validate(tokenizer)
+
+      tokenizer = Tokenizer.set_padding(tokenizer, pad_token: "<pad>")
+
+      assert {:ok, [%{severity: :error, rule: :unknown_pad_token}]} =
+               Tokenizer.validate(tokenizer)
+    end
+
+    test "reports a decoder not reversing the pre-tokenizer", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.set_decoder(tokenizer, Tokenizers.Decoder.byte_level())
+
+      assert {:ok, [%{severity: :warning, rule: :byte_level_mismatch, path: "decoder"}]} =
+               Tokenizer.validate(tokenizer)
+
+      tokenizer =
+        tokenizer
+        |> Tokenizer.set_pre_tokenizer(Tokenizers.PreTokenizer.metaspace())
+        |> Tokenizer.set_decoder(Tokenizers.Decoder.metaspace(replacement: ?_))
+
+      assert {:ok, [%{rule: :metaspace_mismatch, path: "decoder.replacement"}]} =
+               Tokenizer.validate(tokenizer)
+    end
+
+    test "reports special tokens inconsistent with the vocabulary" do
+      {:ok, tokenizer} =
+        "test/fixtures/bert-base-cased.json"
+        |> File.read!()
+        |> String.replace(~r/"ids": \[\s*101/, ~s("ids": [7))
+        |> Tokenizer.from_buffer()
+
+      assert {:ok, [issue]} = Tokenizer.validate(tokenizer)
+
+      assert %{
+               severity: :error,
+               rule: :special_token_id_mismatch,
+               path: "post_processor.special_tokens.[CLS].ids[0]"
+             } = issue
+
+      assert issue.message =~ "[CLS]"
+
+      post_processor =
+        Tokenizers.PostProcessor.sequence([
+          Tokenizers.PostProcessor.byte_level(),
+          Tokenizers.PostProcessor.bert({"[SEP]", 102}, {"[CLS]", 7})
+        ])
+
+      tokenizer = Tokenizer.set_post_processor(tokenizer, post_processor)
+
+      assert {:ok, [issue]} = Tokenizer.validate(tokenizer)
+      assert %{rule: :special_token_id_mismatch, path: "post_processor.processors[1].cls"} = issue
+    end
+  end
+
+  describe "fingerprint/2" do
+    test "does not depend on the formatting of the file", %{tokenizer: tokenizer} do
+      {:ok, compact} =
+        "test/fixtures/bert-base-cased.json"
+        |> File.read!()
+        |> String.replace(~r/\n\s*/, "")
+        |> Tokenizer.from_buffer()
+
+      assert {:ok, fingerprint} = Tokenizer.fingerprint(tokenizer)
+      assert fingerprint =~ ~r/^[0-9a-f]{32}$/
+      assert {:ok, ^fingerprint} = Tokenizer.fingerprint(compact)
+    end
+
+    test "changes with the configuration, unless excluded", %{tokenizer: tokenizer} do
+      other = Tokenizer.set_decoder(tokenizer, Tokenizers.Decoder.byte_level())
+
+      assert {:ok, fingerprint} = Tokenizer.fingerprint(tokenizer)
+      assert {:ok, other_fingerprint} = Tokenizer.fingerprint(other)
+      assert fingerprint != other_fingerprint
+
+      assert Tokenizer.fingerprint(tokenizer, exclude: [:decoder]) ==
+               Tokenizer.fingerprint(other, exclude: [:decoder])
+    end
+
+    test "is available for components", %{tokenizer: tokenizer} do
+      model = Tokenizer.get_model(tokenizer)
+      {:ok, renamed} = Tokenizers.Vocabulary.rename(tokenizer, 1, "<tool>")
+
+      assert {:ok, fingerprint} = Tokenizers.Model.fingerprint(model)
+      assert {:ok, ^fingerprint} = Tokenizers.Model.fingerprint(Tokenizer.get_model(tokenizer))
+      assert {:ok, other} = Tokenizers.Model.fingerprint(Tokenizer.get_model(renamed))
+      assert fingerprint != other
+
+      assert Tokenizers.Normalizer.fingerprint(Tokenizers.Normalizer.lowercase()) ==
+               Tokenizers.Normalizer.fingerprint(Tokenizers.Normalizer.lowercase())
+
+      assert Tokenizers.Decoder.fingerprint(Tokenizers.Decoder.byte_level()) !=
+               Tokenizers.Decoder.fingerprint(Tokenizers.Decoder.metaspace())
+    end
+  end
+
   defp list_to_u32(list) do
     for x <- list, into: <<>>, do: <<x::native-unsigned-32>>
   end
+
+  defp unigram_tokenizer() do
+    vocab = [
+      {"<unk>", 0.0},
+      {"a", -2.0},
+      {"b", -2.5},
+      {"c", -2.2},
+      {"ab", -3.0},
+      {"bc", -3.5},
+      {"abc", -4.5}
+    ]
+
+    {:ok, model} = Tokenizers.Model.Unigram.init(vocab, unk_id: 0, byte_fallback: false)
+    {:ok, tokenizer} = Tokenizer.init(model)
+    Tokenizer.set_pre_tokenizer(tokenizer, Tokenizers.PreTokenizer.whitespace_split())
+  end
 end
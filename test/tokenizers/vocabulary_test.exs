@@ -0,0 +1,227 @@
+defmodule Tokenizers.VocabularyTest do
+  use ExUnit.Case, async: true
+  doctest Tokenizers.Vocabulary
+
+  alias Tokenizers.Encoding
+  alias Tokenizers.Tokenizer
+  alias Tokenizers.Vocabulary
+
+  describe "prune/3" do
+    setup do
+      {:ok, tokenizer} = Tokenizer.from_file("test/fixtures/bert-base-cased.json")
+      %{tokenizer: tokenizer}
+    end
+
+    test "keeps the tokens used by the corpus", %{tokenizer: tokenizer} do
+      corpus = ["Hello world!", "Tokenizers are fun"]
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, corpus)
+
+      # The corpus tokens, plus [PAD], [UNK], [CLS], [SEP] and [MASK]
+      assert Tokenizer.get_vocab_size(pruned.tokenizer) == 14
+
+      {:ok, old} = Tokenizer.encode(tokenizer, {"Tokenizers are fun", "Hello"})
+      {:ok, new} = Tokenizer.encode(pruned.tokenizer, {"Tokenizers are fun", "Hello"})
+
+      assert Encoding.get_tokens(new) == Encoding.get_tokens(old)
+      assert Encoding.get_type_ids(new) == Encoding.get_type_ids(old)
+
+      old_ids = u32_to_list(pruned.old_ids)
+      new_ids = s32_to_list(pruned.new_ids)
+
+      assert length(new_ids) == Tokenizer.get_vocab_size(tokenizer)
+      assert Enum.map(Encoding.get_ids(new), &Enum.at(old_ids, &1)) == Encoding.get_ids(old)
+      assert Enum.map(Encoding.get_ids(old), &Enum.at(new_ids, &1)) == Encoding.get_ids(new)
+      assert Enum.at(new_ids, Tokenizer.token_to_id(tokenizer, "cat")) == -1
+    end
+
+    test "keeps requested tokens and alphabet", %{tokenizer: tokenizer} do
+      assert {:ok, pruned} =
+               Vocabulary.prune(tokenizer, ["Hello"], keep: ["cat"], alphabet: ~c"xy")
+
+      vocab = Tokenizer.get_vocab(pruned.tokenizer)
+      assert Enum.all?(["cat", "x", "y", "##x", "##y"], &Map.has_key?(vocab, &1))
+      assert map_size(vocab) == 11
+    end
+
+    test "keeps BPE merges consistent" do
+      vocab = %{"<unk>" => 0, "a" => 1, "b" => 2, "c" => 3, "ab" => 4, "bc" => 5, "abc" => 6}
+      merges = [{"a", "b"}, {"b", "c"}, {"ab", "c"}]
+      {:ok, model} = Tokenizers.Model.BPE.init(vocab, merges, unk_token: "<unk>")
+      {:ok, tokenizer} = Tokenizer.init(model)
+
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, ["abc"])
+
+      assert Tokenizer.get_vocab(pruned.tokenizer) ==
+               %{"<unk>" => 0, "a" => 1, "b" => 2, "c" => 3, "ab" => 4, "abc" => 5}
+
+      assert Tokenizers.Model.BPE.merges(Tokenizer.get_model(pruned.tokenizer)) ==
+               {:ok, [{"a", "b"}, {"ab", "c"}]}
+
+      assert u32_to_list(pruned.old_ids) == [0, 1, 2, 3, 4, 6]
+      assert s32_to_list(pruned.new_ids) == [0, 1, 2, 3, 4, -1, 5]
+    end
+
+    test "keeps Unigram scores" do
+      vocab = [{"<unk>", 0.0}, {"a", -2.0}, {"b", -2.5}, {"ab", -3.0}]
+      {:ok, model} = Tokenizers.Model.Unigram.init(vocab, unk_id: 0)
+      {:ok, tokenizer} = Tokenizer.init(model)
+
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, ["b"])
+
+      model = Tokenizer.get_model(pruned.tokenizer)
+      assert Tokenizers.Model.Unigram.vocab(model) == {:ok, [{"<unk>", 0.0}, {"b", -2.5}]}
+      assert %{"unk_id" => 0} = Tokenizers.Model.info(model)
+    end
+
+    test "keeps the byte-level alphabet" do
+      alphabet = Enum.map(Tokenizers.PreTokenizer.byte_level_alphabet(), &to_string([&1]))
+      vocab = Map.new(Enum.with_index(alphabet ++ ["ab"]))
+      {:ok, model} = Tokenizers.Model.BPE.init(vocab, [{"a", "b"}])
+      {:ok, tokenizer} = Tokenizer.init(model)
+      tokenizer = Tokenizer.set_pre_tokenizer(tokenizer, Tokenizers.PreTokenizer.byte_level())
+
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, ["ab"])
+      assert Tokenizer.get_vocab_size(pruned.tokenizer) == 257
+
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, ["ab"], keep_bytes: false)
+      assert Tokenizer.get_vocab(pruned.tokenizer) |> Map.keys() |> Enum.sort() == ~w(a ab b Ġ)
+    end
+
+    test "keeps the pad token" do
+      vocab = %{"<unk>" => 0, "a" => 1, "b" => 2, "<pad>" => 3}
+      {:ok, model} = Tokenizers.Model.BPE.init(vocab, [], unk_token: "<unk>")
+      {:ok, tokenizer} = Tokenizer.init(model)
+      tokenizer = Tokenizer.set_padding(tokenizer, pad_token: "<pad>", pad_id: 3)
+
+      assert {:ok, pruned} = Vocabulary.prune(tokenizer, ["b"])
+      assert Tokenizer.get_vocab(pruned.tokenizer) == %{"<unk>" => 0, "b" => 1, "<pad>" => 2}
+
+      {:ok, [encoding, _]} = Tokenizer.encode_batch(pruned.tokenizer, ["b", "bb"])
+      assert Encoding.get_ids(encoding) == [1, 2]
+    end
+  end
+
+  describe "extend/4" do
+    test "appends WordPiece tokens learned from the corpus" do
+      {:ok, tokenizer} = Tokenizer.from_file("test/fixtures/bert-base-cased.json")
+      corpus = ["Tokenizers tokenize. Tokenizers are fun", "Tokenizers [MASK] rock"]
+
+      assert {:ok, extended} = Vocabulary.extend(tokenizer, corpus, 3)
+
+      assert extended.tokens ==
+               [{"##izers", 28996}, {"##kenizers", 28997}, {"Tokenizers", 28998}]
+
+      assert Tokenizer.get_vocab_size(extended.tokenizer) == 28999
+
+      {:ok, encoding} = Tokenizer.encode(extended.tokenizer, "Tokenizers are fun")
+      assert Encoding.get_tokens(encoding) == ["[CLS]", "Tokenizers", "are", "fun", "[SEP]"]
+      assert Encoding.get_ids(encoding) == [101, 28998, 1132, 4106, 102]
+    end
+
+    test "appends BPE merges and keeps added token ids" do
+      vocab = %{"<unk>" => 0, "a" => 1, "b" => 2, "c" => 3, "ab" => 4, "abc" => 5, "d" => 6}
+      merges = [{"a", "b"}, {"ab", "c"}]
+      {:ok, model} = Tokenizers.Model.BPE.init(vocab, merges, unk_token: "<unk>")
+      {:ok, tokenizer} = Tokenizer.init(model)
+      pre_tokenizer = Tokenizers.PreTokenizer.whitespace_split()
+      tokenizer = Tokenizer.set_pre_tokenizer(tokenizer, pre_tokenizer)
+      tokenizer = Tokenizer.add_special_tokens(tokenizer, ["<s>"])
+
+      assert {:ok, extended} = Vocabulary.extend(tokenizer, ["abcd abcd <s> dd d"], 5)
+
+      assert extended.tokens == [{"abcd", 8}]
+      assert Tokenizer.token_to_id(extended.tokenizer, "<s>") == 7
+
+      assert Tokenizers.Model.BPE.merges(Tokenizer.get_model(extended.tokenizer)) ==
+               {:ok, [{"a", "b"}, {"ab", "c"}, {"abc", "d"}]}
+
+      {:ok, encoding} = Tokenizer.encode(extended.tokenizer, "<s> abcd")
+      assert Encoding.get_ids(encoding) == [7, 8]
+    end
+
+    test "scores Unigram tokens by frequency" do
+      vocab = [{"<unk>", 0.0}, {"a", -2.0}, {"b", -2.5}, {"ab", -3.0}]
+      {:ok, model} = Tokenizers.Model.Unigram.init(vocab, unk_id: 0)
+      {:ok, tokenizer} = Tokenizer.init(model)
+      pre_tokenizer = Tokenizers.PreTokenizer.whitespace_split()
+      tokenizer = Tokenizer.set_pre_tokenizer(tokenizer, pre_tokenizer)
+
+      assert {:ok, extended} = Vocabulary.extend(tokenizer, ["abb abb ba"], 5)
+      assert extended.tokens == [{"abb", 4}]
+
+      model = Tokenizer.get_model(extended.tokenizer)
+      assert {:ok, [_, _, _, _, {"abb", score}]} = Tokenizers.Model.Unigram.vocab(model)
+      # 2 occurrences among the 4 tokens left, "abb", "abb", "b" and "a",
+      # clamped to the highest score besides the unknown token
+      assert_in_delta score, -2.0, 1.0e-6
+
+      {:ok, encoding} = Tokenizer.encode(extended.tokenizer, "abb ab")
+      assert Encoding.get_tokens(encoding) == ["abb", "ab"]
+      assert Encoding.get_ids(encoding) == [4, 3]
+    end
+
+    test "rejects WordLevel models" do
+      {:ok, model} = Tokenizers.Model.WordLevel.init(%{"a" => 0}, unk_token: "a")
+      {:ok, tokenizer} = Tokenizer.init(model)
+      assert {:error, _} = Vocabulary.extend(tokenizer, ["a a"], 1)
+    end
+  end
+
+  describe "surgery" do
+    setup do
+      {:ok, tokenizer} = Tokenizer.from_file("test/fixtures/bert-base-cased.json")
+      %{tokenizer: tokenizer}
+    end
+
+    @tag :tmp_dir
+    test "repurposes a model token as a special token", %{tokenizer: tokenizer} = config do
+      assert Tokenizer.id_to_token(tokenizer, 1) == "[unused1]"
+
+      assert {:ok, tokenizer} = Vocabulary.rename(tokenizer, 1, "<tool>")
+      assert {:ok, tokenizer} = Vocabulary.update_added_token(tokenizer, 1, special: true)
+
+      assert Tokenizer.token_to_id(tokenizer, "<tool>") == 1
+      assert Tokenizer.token_to_id(tokenizer, "[unused1]") == nil
+      assert Map.get(Tokenizer.get_vocab(tokenizer), "<tool>") == 1
+
+      {:ok, encoding} = Tokenizer.encode(tokenizer, "a <tool> b")
+      assert Encoding.get_tokens(encoding) == ["[CLS]", "a", "<tool>", "b", "[SEP]"]
+      assert Tokenizer.decode(tokenizer, Encoding.get_ids(encoding)) == {:ok, "a b"}
+
+      {:ok, json} = Tokenizer.save(tokenizer, Path.join(config.tmp_dir, "surgery.json"))
+      {:ok, loaded} = Tokenizer.from_file(json)
+      assert Tokenizer.token_to_id(loaded, "<tool>") == 1
+    end
+
+    test "renames special tokens of the post-processor", %{tokenizer: tokenizer} do
+      assert {:ok, tokenizer} = Vocabulary.rename(tokenizer, 101, "<bos>")
+
+      {:ok, encoding} = Tokenizer.encode(tokenizer, "a")
+      assert Encoding.get_tokens(encoding) == ["<bos>", "a", "[SEP]"]
+      assert Encoding.get_ids(encoding) == [101, 170, 102]
+    end
+
+    test "rejects renaming to an existing token", %{tokenizer: tokenizer} do
+      assert {:error, _} = Vocabulary.rename(tokenizer, 102, "a")
+    end
+
+    test "removes added tokens", %{tokenizer: tokenizer} do
+      tokenizer = Tokenizer.add_special_tokens(tokenizer, ["<r0>", "<r1>"])
+
+      assert {:error, _} = Vocabulary.remove_added_tokens(tokenizer, [28996])
+
+      assert {:ok, removed} = Vocabulary.remove_added_tokens(tokenizer, [28997])
+      assert Tokenizer.token_to_id(removed, "<r1>") == nil
+      assert Tokenizer.get_vocab_size(removed) == 28997
+
+      assert {:ok, removed} = Vocabulary.remove_added_tokens(tokenizer, [103])
+      assert Tokenizer.token_to_id(removed, "[MASK]") == 103
+      {:ok, encoding} = Tokenizer.encode(removed, "[MASK]")
+      refute "[MASK]" in Encoding.get_tokens(encoding)
+    end
+  end
+
+  defp u32_to_list(binary), do: for(<<x::native-unsigned-32 <- binary>>, do: x)
+
+  defp s32_to_list(binary), do: for(<<x::native-signed-32 <- binary>>, do: x)
+end